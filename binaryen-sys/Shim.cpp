@@ -1,8 +1,17 @@
+#include <algorithm>
 #include <cstddef>
+#include <cstdio>
+#include <cstdlib>
 #include <cstring>
+#include <iostream>
+#include <memory>
+#include <mutex>
+#include <sstream>
+#include <unordered_map>
 
 #include "wrapper.h"
 #include "asm_v_wasm.h"
+#include "support/colors.h"
 #include "support/file.h"
 #include "pass.h"
 #include "tools/optimization-options.h"
@@ -13,10 +22,173 @@
 #include "wasm-validator.h" // For WasmValidator
 
 #include "wasm-binary.h"    // For SafeRead
+#include "wasm-s-parser.h"  // For SafeParse
+#include "wasm-traversal.h" // For CountInstructions
+#include "ir/module-utils.h" // For renameFunction and copyModule
 
 using namespace wasm;
 using namespace std;
 
+// Copies `str` into a malloc'ed, NUL-terminated buffer that should be released
+// with BinaryenShimDisposeString.
+static char* copyToMallocedString(const string& str) {
+  char* result = (char*)malloc(str.size() + 1);
+  memcpy(result, str.c_str(), str.size() + 1);
+  return result;
+}
+
+// Binaryen is built with THROW_ON_FATAL, see build.rs, so its fatal errors are
+// exceptions. They must not unwind into the Rust caller. Entry points that can
+// report them catch them themselves; the others run through this, which prints
+// the message to stderr and aborts the process on a fatal error. stderr is used
+// directly, as std::cerr may be redirected at that point.
+template <typename F> static auto abortOnFatal(F body) -> decltype(body()) {
+  try {
+    return body();
+  } catch (std::exception& e) {
+    fprintf(stderr, "Fatal: %s\n", e.what());
+  } catch (...) {
+    fprintf(stderr, "Fatal: unknown error\n");
+  }
+  abort();
+}
+
+namespace {
+
+enum StreamId { StreamCout, StreamCerr, NumStreams };
+
+// The stream the calling thread's writes to std::cout and std::cerr go to
+// instead, see ScopedCapture.
+thread_local std::ostream* captureTargets[NumStreams] = {};
+
+// A stream buffer installed once on std::cout or std::cerr, see
+// forwardingBuf. Writes of threads capturing the stream go to their capture
+// target. The others go to the diagnostic callback line by line if one is set,
+// and to the original buffer of the stream otherwise.
+//
+// The buffer is never swapped out or freed, so threads that are still writing
+// to it, e.g. workers of a running pass, never see it go away. Only the
+// callback is replaced, under the mutex.
+class ForwardingStreamBuf : public std::streambuf {
+public:
+  ForwardingStreamBuf(StreamId id, std::streambuf* original)
+    : id(id), original(original) {}
+
+  void setCallback(BinaryenShimDiagnosticCallback newCallback) {
+    std::lock_guard<std::mutex> lock(mutex);
+    if (callback && !line.empty()) {
+      callback(line.data(), line.size());
+    }
+    line.clear();
+    callback = newCallback;
+  }
+
+protected:
+  int overflow(int c) override {
+    if (c != EOF) {
+      char ch = char(c);
+      forward(&ch, 1);
+    }
+    return traits_type::not_eof(c);
+  }
+
+  std::streamsize xsputn(const char* s, std::streamsize n) override {
+    forward(s, n);
+    return n;
+  }
+
+  int sync() override {
+    if (captureTargets[id]) {
+      return 0;
+    }
+    std::lock_guard<std::mutex> lock(mutex);
+    return original->pubsync();
+  }
+
+private:
+  void forward(const char* s, std::streamsize n) {
+    if (auto* target = captureTargets[id]) {
+      target->write(s, n);
+      return;
+    }
+    std::lock_guard<std::mutex> lock(mutex);
+    if (!callback) {
+      original->sputn(s, n);
+      return;
+    }
+    for (std::streamsize i = 0; i < n; i++) {
+      if (s[i] == '\n') {
+        callback(line.data(), line.size());
+        line.clear();
+      } else {
+        line.push_back(s[i]);
+      }
+    }
+  }
+
+  StreamId id;
+  std::streambuf* original;
+  BinaryenShimDiagnosticCallback callback = nullptr;
+  std::string line;
+  std::mutex mutex;
+};
+
+ForwardingStreamBuf& forwardingBuf(StreamId id) {
+  static ForwardingStreamBuf* bufs[NumStreams] = {
+    new ForwardingStreamBuf(StreamCout, cout.rdbuf()),
+    new ForwardingStreamBuf(StreamCerr, cerr.rdbuf()),
+  };
+  static std::once_flag installed;
+  std::call_once(installed, [] {
+    cout.rdbuf(bufs[StreamCout]);
+    cerr.rdbuf(bufs[StreamCerr]);
+  });
+  return *bufs[id];
+}
+
+// Sends what the calling thread writes to std::cout or std::cerr to `target`
+// while alive. Other threads keep writing where they did, so the global
+// streams are never swapped.
+class ScopedCapture {
+public:
+  ScopedCapture(StreamId id, std::ostream& target)
+    : id(id), previous(captureTargets[id]) {
+    forwardingBuf(id);
+    captureTargets[id] = &target;
+  }
+  ~ScopedCapture() { captureTargets[id] = previous; }
+
+private:
+  StreamId id;
+  std::ostream* previous;
+};
+
+// Whether Binaryen prints escape codes is process-global, so it is only turned
+// off under this lock, which all shim functions doing so share. Output printed
+// by other threads meanwhile is printed without colors.
+std::mutex colorsMutex;
+
+class ScopedNoColors {
+public:
+  ScopedNoColors() : lock(colorsMutex), colors(Colors::isEnabled()) {
+    Colors::setEnabled(false);
+  }
+  ~ScopedNoColors() { Colors::setEnabled(colors); }
+
+private:
+  std::lock_guard<std::mutex> lock;
+  bool colors;
+};
+
+} // anonymous namespace
+
+// Routes everything Binaryen prints to std::cerr to `callback`, line by line.
+// Passing NULL restores printing to stderr. Once this returns, the previous
+// callback is not called anymore.
+extern "C" void BinaryenShimSetDiagnosticCallback(BinaryenShimDiagnosticCallback callback) {
+  forwardingBuf(StreamCerr).setCallback(callback);
+}
+
 // NOTE: this is based on BinaryenModuleRead from binaryen-c.cpp
 extern "C" BinaryenModuleRef BinaryenModuleSafeRead(const char* input, size_t inputSize) {
     auto* wasm = new Module;
@@ -26,12 +198,18 @@ extern "C" BinaryenModuleRef BinaryenModuleSafeRead(const char* input, size_t in
         parser.read();
     } catch (ParseException const&) {
         // FIXME: support passing back the exception text
+        delete wasm;
+        return NULL;
+    } catch (std::exception const&) {
+        // A fatal error, see THROW_ON_FATAL in build.rs.
+        delete wasm;
         return NULL;
     }
     return wasm;
 }
 
 extern "C" BinaryenModuleRef translateToFuzz(const char *data, size_t len, bool emitAtomics) {
+  return abortOnFatal([&]() -> BinaryenModuleRef {
     auto module = new Module();
 
     vector<char> input(data, data + len);
@@ -44,6 +222,27 @@ extern "C" BinaryenModuleRef translateToFuzz(const char *data, size_t len, bool
     reader.build();
 
     return module;
+  });
+}
+
+// Like translateToFuzz, but with the given features enabled on the module. The
+// fuzzer only emits what the enabled features allow. The flag of translateToFuzz
+// only enables atomics on top of the default features.
+extern "C" BinaryenModuleRef BinaryenShimTranslateToFuzz(
+    const char* data, size_t len, BinaryenFeatures features
+) {
+  return abortOnFatal([&]() -> BinaryenModuleRef {
+    auto module = new Module();
+
+    vector<char> input(data, data + len);
+
+    TranslateToFuzzReader reader(*module, std::move(input));
+    module->features = FeatureSet(features);
+    module->hasFeaturesSection = true;
+    reader.build();
+
+    return module;
+  });
 }
 
 extern "C" void BinaryenShimDisposeBinaryenModuleAllocateAndWriteResult(
@@ -57,36 +256,671 @@ extern "C" void BinaryenShimDisposeBinaryenModuleAllocateAndWriteResult(
     }
 }
 
+// NOTE: this is based on BinaryenModuleParse from binaryen-c.cpp
+// Unlike the original, a parse error doesn't abort the process. Instead NULL is
+// returned and `error` is set to a description of the problem, which should be
+// released with BinaryenShimDisposeString.
+extern "C" BinaryenModuleRef BinaryenModuleSafeParse(const char* text, char** error) {
+  auto* wasm = new Module;
+  *error = NULL;
+  try {
+    SExpressionParser parser(text);
+    Element& root = *parser.root;
+    if (root.size() == 0) {
+      throw ParseException("no module found");
+    }
+    SExpressionWasmBuilder builder(*wasm, *root[0], IRProfile::Normal);
+  } catch (ParseException& p) {
+    ostringstream message;
+    message << p.text;
+    if (p.line != size_t(-1)) {
+      message << " at " << p.line << ":" << p.col;
+    }
+    *error = copyToMallocedString(message.str());
+  } catch (std::exception& e) {
+    *error = copyToMallocedString(e.what());
+  }
+  if (*error) {
+    delete wasm;
+    return NULL;
+  }
+  return wasm;
+}
+
+// NOTE: this is based on BinaryenModuleAllocateAndWrite from binaryen-c.cpp
+// Unlike the original, emitting the names section is controlled by the caller
+// instead of the global debugInfo setting. On a fatal error, e.g. for a module
+// that can't be encoded, an empty result is returned and `error` is set to the
+// message, which should be released with BinaryenShimDisposeString.
+extern "C" BinaryenModuleAllocateAndWriteResult BinaryenModuleAllocateAndWriteWithSettings(
+    BinaryenModuleRef module, const char* sourceMapUrl, int debugInfo, char** error
+) {
+  Module* wasm = (Module*)module;
+  BufferWithRandomAccess buffer;
+  std::stringstream ss;
+  *error = NULL;
+  try {
+    WasmBinaryWriter writer(wasm, buffer);
+    writer.setNamesSection(debugInfo != 0);
+    if (sourceMapUrl) {
+      writer.setSourceMap(&ss, sourceMapUrl);
+    }
+    writer.write();
+  } catch (std::exception& e) {
+    *error = copyToMallocedString(e.what());
+    return {NULL, 0, NULL};
+  }
+  void* binary = malloc(buffer.size());
+  std::copy_n(buffer.begin(), buffer.size(), static_cast<char*>(binary));
+  char* sourceMap = NULL;
+  if (sourceMapUrl) {
+    sourceMap = copyToMallocedString(ss.str());
+  }
+  return {binary, buffer.size(), sourceMap};
+}
+
+// Renames the function with the given index, updating all references to it,
+// and marks the name as explicit so that it ends up in the names section.
+// Returns 0 if another function already has this name.
+extern "C" int BinaryenModuleSetFunctionName(
+    BinaryenModuleRef module, BinaryenIndex index, const char* name
+) {
+  return abortOnFatal([&]() -> int {
+    Module* wasm = (Module*)module;
+    Name newName(name);
+    Function* func = wasm->functions[index].get();
+    if (func->name != newName) {
+      if (wasm->getFunctionOrNull(newName)) {
+        return 0;
+      }
+      ModuleUtils::renameFunction(*wasm, func->name, newName);
+    }
+    func->hasExplicitName = true;
+    return 1;
+  });
+}
+
+// Sets the `numArguments` pass arguments given as parallel `names` and
+// `values` arrays on `options`.
+//
+// The arguments are passed with every call instead of being kept in global
+// state, like BinaryenSetPassArgument of binaryen-c.cpp does, so that passes
+// running on different threads don't see each other's arguments.
+static void setPassArguments(
+    PassOptions& options, const char** names, const char** values,
+    BinaryenIndex numArguments
+) {
+  for (BinaryenIndex i = 0; i < numArguments; i++) {
+    options.arguments[names[i]] = values[i];
+  }
+}
+
+// The pass options for the given settings, see
+// BinaryenModuleRunPassesWithSettings.
+static PassOptions makePassOptions(
+    int shrinkLevel, int optimizeLevel, int debugInfo, int zeroFilledMemory,
+    int alwaysInlineMaxSize, int flexibleInlineMaxSize, int oneCallerInlineMaxSize,
+    int closedWorld
+) {
+  PassOptions options = PassOptions::getWithDefaultOptimizationOptions();
+  options.shrinkLevel = shrinkLevel;
+  options.optimizeLevel = optimizeLevel;
+  options.debugInfo = debugInfo != 0;
+  options.zeroFilledMemory = zeroFilledMemory != 0;
+  options.closedWorld = closedWorld != 0;
+  // Negative sizes keep the defaults.
+  if (alwaysInlineMaxSize >= 0) {
+    options.inlining.alwaysInlineMaxSize = alwaysInlineMaxSize;
+  }
+  if (flexibleInlineMaxSize >= 0) {
+    options.inlining.flexibleInlineMaxSize = flexibleInlineMaxSize;
+  }
+  if (oneCallerInlineMaxSize >= 0) {
+    options.inlining.oneCallerInlineMaxSize = oneCallerInlineMaxSize;
+  }
+  return options;
+}
+
 // NOTE: this is based on BinaryenModuleRunPasses and BinaryenModuleOptimizer
 // from binaryen-c.cpp
-// Main benefit is being thread safe.
-extern "C" void BinaryenModuleRunPassesWithSettings(
+// Main benefit is being thread safe: the settings and the pass arguments, given
+// as parallel `argumentNames` and `argumentValues` arrays, only apply to this
+// run.
+//
+// Binaryen is built with THROW_ON_FATAL, so fatal errors of passes are caught
+// here instead of exiting the process. The message is returned in that case (it
+// should be released with BinaryenShimDisposeString), and NULL on success. The
+// module may be left half transformed. Only errors thrown on the calling thread
+// are caught: Binaryen's worker threads, on which function-parallel passes run,
+// don't catch them, so they end in std::terminate.
+extern "C" char* BinaryenModuleRunPassesWithSettings(
     BinaryenModuleRef module, const char** passes, BinaryenIndex numPasses,
-    int shrinkLevel, int optimizeLevel, int debugInfo
+    const char** argumentNames, const char** argumentValues,
+    BinaryenIndex numArguments, int shrinkLevel, int optimizeLevel, int debugInfo,
+    int zeroFilledMemory, int alwaysInlineMaxSize, int flexibleInlineMaxSize,
+    int oneCallerInlineMaxSize, int closedWorld
 ) {
   Module* wasm = (Module*)module;
   PassRunner passRunner(wasm);
-  passRunner.options = PassOptions::getWithDefaultOptimizationOptions();
-  passRunner.options.shrinkLevel = shrinkLevel;
-  passRunner.options.optimizeLevel = optimizeLevel;
-  passRunner.options.debugInfo = debugInfo != 0;
-  if (passes == nullptr) {
-    passRunner.addDefaultOptimizationPasses();
-  } else {
-    for (BinaryenIndex i = 0; i < numPasses; i++) {
-      passRunner.add(passes[i]);
+  passRunner.options = makePassOptions(
+    shrinkLevel, optimizeLevel, debugInfo, zeroFilledMemory, alwaysInlineMaxSize,
+    flexibleInlineMaxSize, oneCallerInlineMaxSize, closedWorld);
+  setPassArguments(
+    passRunner.options, argumentNames, argumentValues, numArguments);
+  try {
+    if (passes == nullptr) {
+      passRunner.addDefaultOptimizationPasses();
+    } else {
+      for (BinaryenIndex i = 0; i < numPasses; i++) {
+        passRunner.add(passes[i]);
+      }
     }
+    passRunner.run();
+  } catch (std::exception& e) {
+    return copyToMallocedString(e.what());
   }
-  passRunner.run();
+  return NULL;
+}
+
+// Like BinaryenModuleRunPassesWithSettings, but runs the passes on a single
+// function of the module. With `passes` being NULL, the function-level passes of
+// the default optimization pipeline are run. Passes that only work on whole
+// modules are not allowed.
+extern "C" char* BinaryenShimFunctionRunPassesWithSettings(
+    BinaryenFunctionRef func, BinaryenModuleRef module, const char** passes,
+    BinaryenIndex numPasses, int shrinkLevel, int optimizeLevel, int debugInfo,
+    int zeroFilledMemory, int alwaysInlineMaxSize, int flexibleInlineMaxSize,
+    int oneCallerInlineMaxSize, int closedWorld
+) {
+  Module* wasm = (Module*)module;
+  PassRunner passRunner(wasm);
+  passRunner.options = makePassOptions(
+    shrinkLevel, optimizeLevel, debugInfo, zeroFilledMemory, alwaysInlineMaxSize,
+    flexibleInlineMaxSize, oneCallerInlineMaxSize, closedWorld);
+  try {
+    if (passes == nullptr) {
+      passRunner.addDefaultFunctionOptimizationPasses();
+    } else {
+      for (BinaryenIndex i = 0; i < numPasses; i++) {
+        passRunner.add(passes[i]);
+      }
+    }
+    passRunner.runOnFunction((Function*)func);
+  } catch (std::exception& e) {
+    return copyToMallocedString(e.what());
+  }
+  return NULL;
+}
+
+// Runs `pass` on the module with the given pass arguments, see
+// BinaryenModuleRunPassesWithSettings, and returns what it prints to std::cout,
+// like the print-* passes do. Only the output of the calling thread is
+// captured, which such passes print on as they work on the whole module;
+// other threads keep printing to stdout. The output should be released with
+// BinaryenShimDisposeString. On a fatal error NULL is returned and `error` is
+// set to the message, which should be released the same way.
+extern "C" char* BinaryenShimRunPassCapturingOutput(
+    BinaryenModuleRef module, const char* pass, const char** argumentNames,
+    const char** argumentValues, BinaryenIndex numArguments, char** error
+) {
+  Module* wasm = (Module*)module;
+  PassRunner passRunner(wasm);
+  setPassArguments(
+    passRunner.options, argumentNames, argumentValues, numArguments);
+
+  ostringstream output;
+  try {
+    // Don't let escape codes end up in the output.
+    ScopedNoColors noColors;
+    ScopedCapture capture(StreamCout, output);
+    passRunner.add(pass);
+    passRunner.run();
+  } catch (std::exception& e) {
+    *error = copyToMallocedString(e.what());
+    return NULL;
+  }
+  return copyToMallocedString(output.str());
 }
 
 // NOTE: this is based on BinaryenModuleValidate from binaryen-c.cpp
 extern "C" int BinaryenModuleSafeValidate(BinaryenModuleRef module) {
-  Module* wasm = (Module*)module;
-  auto features = wasm->features;
-  // TODO(tlively): Add C API for managing features
-  wasm->features = FeatureSet::All;
-  auto ret = WasmValidator().validate(*wasm) ? 1 : 0;
-  wasm->features = features;
-  return ret;
+  return abortOnFatal([&]() -> int {
+    Module* wasm = (Module*)module;
+    auto features = wasm->features;
+    // TODO(tlively): Add C API for managing features
+    wasm->features = FeatureSet::All;
+    auto ret = WasmValidator().validate(*wasm) ? 1 : 0;
+    wasm->features = features;
+    return ret;
+  });
+}
+
+// NOTE: this is similar to BinaryenModuleSafeValidate, but instead of letting
+// the validator print errors to stderr, they are captured and returned.
+// Returns NULL if the module is valid.
+extern "C" char* BinaryenModuleSafeValidateWithFeaturesAndMessage(
+    BinaryenModuleRef module, BinaryenFeatures enabled
+) {
+  return abortOnFatal([&]() -> char* {
+    Module* wasm = (Module*)module;
+    auto features = wasm->features;
+    wasm->features = FeatureSet(enabled);
+
+    // The validator collects the errors of all functions and prints them on
+    // the calling thread, so capturing that thread's writes gets all of them.
+    ostringstream errors;
+    bool valid;
+    {
+      // Don't let escape codes end up in the message.
+      ScopedNoColors noColors;
+      ScopedCapture capture(StreamCerr, errors);
+      valid = WasmValidator().validate(*wasm);
+    }
+
+    wasm->features = features;
+    if (valid) {
+      return NULL;
+    }
+    return copyToMallocedString(errors.str());
+  });
+}
+
+extern "C" char* BinaryenModuleSafeValidateWithMessage(BinaryenModuleRef module) {
+  return BinaryenModuleSafeValidateWithFeaturesAndMessage(module, FeatureSet::All);
+}
+
+extern "C" void BinaryenShimDisposeString(char* str) {
+  free(str);
+}
+
+// NOTE: this is similar to BinaryenModuleSafeValidate, but validates under the
+// given feature set and doesn't print anything.
+extern "C" int BinaryenModuleSafeValidateWithFeatures(
+    BinaryenModuleRef module, BinaryenFeatures features
+) {
+  return abortOnFatal([&]() -> int {
+    Module* wasm = (Module*)module;
+    auto oldFeatures = wasm->features;
+    wasm->features = FeatureSet(features);
+    auto flags = WasmValidator::Globally | WasmValidator::Quiet;
+    auto ret = WasmValidator().validate(*wasm, flags) ? 1 : 0;
+    wasm->features = oldFeatures;
+    return ret;
+  });
+}
+
+// Copies functions, globals, tags, memories, tables, segments and exports of
+// `otherModule` into `module`. The start function of `otherModule` is not
+// copied.
+//
+// Nothing is copied if any of them would clash with one already present in
+// `module`. In that case the clashing name is returned (it should be released
+// with BinaryenShimDisposeString) and `exportClash` is set if it's the name
+// of an export. Returns NULL on success.
+extern "C" char* BinaryenModuleMerge(
+    BinaryenModuleRef module, BinaryenModuleRef otherModule, int* exportClash
+) {
+  return abortOnFatal([&]() -> char* {
+    Module* wasm = (Module*)module;
+    Module* other = (Module*)otherModule;
+    *exportClash = 0;
+
+    auto clash = [](Name name) { return copyToMallocedString(name.toString()); };
+    for (auto& func : other->functions) {
+      if (wasm->getFunctionOrNull(func->name)) {
+        return clash(func->name);
+      }
+    }
+    for (auto& global : other->globals) {
+      if (wasm->getGlobalOrNull(global->name)) {
+        return clash(global->name);
+      }
+    }
+    for (auto& tag : other->tags) {
+      if (wasm->getTagOrNull(tag->name)) {
+        return clash(tag->name);
+      }
+    }
+    for (auto& memory : other->memories) {
+      if (wasm->getMemoryOrNull(memory->name)) {
+        return clash(memory->name);
+      }
+    }
+    for (auto& table : other->tables) {
+      if (wasm->getTableOrNull(table->name)) {
+        return clash(table->name);
+      }
+    }
+    for (auto& segment : other->dataSegments) {
+      if (wasm->getDataSegmentOrNull(segment->name)) {
+        return clash(segment->name);
+      }
+    }
+    for (auto& segment : other->elementSegments) {
+      if (wasm->getElementSegmentOrNull(segment->name)) {
+        return clash(segment->name);
+      }
+    }
+    for (auto& exp : other->exports) {
+      if (wasm->getExportOrNull(exp->name)) {
+        *exportClash = 1;
+        return clash(exp->name);
+      }
+    }
+
+    for (auto& func : other->functions) {
+      ModuleUtils::copyFunction(func.get(), *wasm);
+    }
+    for (auto& global : other->globals) {
+      ModuleUtils::copyGlobal(global.get(), *wasm);
+    }
+    for (auto& tag : other->tags) {
+      ModuleUtils::copyTag(tag.get(), *wasm);
+    }
+    for (auto& memory : other->memories) {
+      ModuleUtils::copyMemory(memory.get(), *wasm);
+    }
+    for (auto& table : other->tables) {
+      ModuleUtils::copyTable(table.get(), *wasm);
+    }
+    for (auto& segment : other->dataSegments) {
+      ModuleUtils::copyDataSegment(segment.get(), *wasm);
+    }
+    for (auto& segment : other->elementSegments) {
+      ModuleUtils::copyElementSegment(segment.get(), *wasm);
+    }
+    for (auto& exp : other->exports) {
+      wasm->addExport(std::make_unique<Export>(*exp));
+    }
+    wasm->features |= other->features;
+    return NULL;
+  });
+}
+
+// Pass registry access, so that passes can be listed and checked against what
+// the linked Binaryen actually supports.
+
+extern "C" BinaryenIndex BinaryenShimGetNumRegisteredPasses() {
+  return abortOnFatal([&]() -> BinaryenIndex {
+    return PassRegistry::get()->getRegisteredNames().size();
+  });
+}
+
+// Returns the name of the pass with the given index in alphabetical order. The
+// result should be released with BinaryenShimDisposeString.
+extern "C" char* BinaryenShimGetRegisteredPassName(BinaryenIndex index) {
+  return abortOnFatal([&]() -> char* {
+    auto names = PassRegistry::get()->getRegisteredNames();
+    if (index >= names.size()) {
+      return NULL;
+    }
+    return copyToMallocedString(names[index]);
+  });
+}
+
+extern "C" int BinaryenShimIsPassRegistered(const char* name) {
+  return abortOnFatal([&]() -> int {
+    return PassRegistry::get()->containsPass(name) ? 1 : 0;
+  });
+}
+
+extern "C" int BinaryenShimIsPassHidden(const char* name) {
+  return abortOnFatal([&]() -> int {
+    auto* registry = PassRegistry::get();
+    return registry->containsPass(name) && registry->isPassHidden(name) ? 1 : 0;
+  });
+}
+
+// Returns whether the pass can run on a single function, which
+// PassRunner::runOnFunction requires of all its passes; it hits a fatal error
+// on the others, e.g. `inlining`. Returns 0 if there is no such pass.
+extern "C" int BinaryenShimIsPassFunctionParallel(const char* name) {
+  return abortOnFatal([&]() -> int {
+    auto* registry = PassRegistry::get();
+    if (!registry->containsPass(name)) {
+      return 0;
+    }
+    return registry->createPass(name)->isFunctionParallel() ? 1 : 0;
+  });
+}
+
+// Returns NULL if there is no such pass. The result should be released with
+// BinaryenShimDisposeString.
+extern "C" char* BinaryenShimGetPassDescription(const char* name) {
+  return abortOnFatal([&]() -> char* {
+    auto* registry = PassRegistry::get();
+    if (!registry->containsPass(name)) {
+      return NULL;
+    }
+    return copyToMallocedString(registry->getPassDescription(name));
+  });
+}
+
+// Merges active data segments so that the module has at most `max` segments.
+// Binaryen's limit-segments pass only applies the fixed limit of web engines,
+// so this is done here. Passive segments are kept as they are; the active ones
+// with the highest offsets are merged into one, with the gaps between them
+// filled with zeros.
+//
+// All active segments must belong to the same memory and have constant,
+// non-overlapping offsets. Returns 0 if that's not the case or if the passive
+// segments alone exceed `max`, in which case the module is left unchanged.
+//
+// References to the merged segments, e.g. by data.drop and memory.init, are
+// redirected to the segment they were merged into. All of them are active and
+// so dropped on instantiation, which keeps the behavior the same.
+extern "C" int BinaryenShimLimitDataSegments(
+    BinaryenModuleRef module, BinaryenIndex max
+) {
+  return abortOnFatal([&]() -> int {
+    Module* wasm = (Module*)module;
+    auto& segments = wasm->dataSegments;
+    if (segments.size() <= max) {
+      return 1;
+    }
+
+    std::vector<DataSegment*> active;
+    for (auto& segment : segments) {
+      if (segment->isPassive) {
+        continue;
+      }
+      if (!segment->offset->is<Const>() ||
+          segment->memory != segments[0]->memory) {
+        return 0;
+      }
+      active.push_back(segment.get());
+    }
+    size_t numPassive = segments.size() - active.size();
+    if (numPassive >= max) {
+      return 0;
+    }
+    // The active segments to keep, counting the one the others are merged into.
+    size_t numKept = max - numPassive;
+
+    auto offsetOf = [](DataSegment* segment) {
+      return segment->offset->cast<Const>()->value.getUnsigned();
+    };
+    std::stable_sort(active.begin(), active.end(), [&](auto* a, auto* b) {
+      return offsetOf(a) < offsetOf(b);
+    });
+    for (size_t i = 1; i < active.size(); i++) {
+      if (offsetOf(active[i - 1]) + active[i - 1]->data.size() >
+          offsetOf(active[i])) {
+        return 0;
+      }
+    }
+
+    auto* merged = active[numKept - 1];
+    auto start = offsetOf(merged);
+    std::unordered_map<Name, Name> renames;
+    for (size_t i = numKept; i < active.size(); i++) {
+      auto* segment = active[i];
+      merged->data.resize(offsetOf(segment) - start, 0);
+      merged->data.insert(
+        merged->data.end(), segment->data.begin(), segment->data.end());
+      renames[segment->name] = merged->name;
+    }
+
+    struct Redirect : public PostWalker<Redirect> {
+      std::unordered_map<Name, Name>& renames;
+      Redirect(std::unordered_map<Name, Name>& renames) : renames(renames) {}
+      void redirect(Name& segment) {
+        auto it = renames.find(segment);
+        if (it != renames.end()) {
+          segment = it->second;
+        }
+      }
+      void visitMemoryInit(MemoryInit* curr) { redirect(curr->segment); }
+      void visitDataDrop(DataDrop* curr) { redirect(curr->segment); }
+      void visitArrayNewData(ArrayNewData* curr) { redirect(curr->segment); }
+      void visitArrayInitData(ArrayInitData* curr) { redirect(curr->segment); }
+    };
+    Redirect redirect(renames);
+    redirect.walkModule(wasm);
+
+    segments.erase(
+      std::remove_if(segments.begin(), segments.end(),
+        [&](auto& segment) { return renames.count(segment->name) > 0; }),
+      segments.end());
+    wasm->updateDataSegmentsMap();
+    return 1;
+  });
+}
+
+// Counts the instructions in the bodies of the functions defined by the module.
+extern "C" size_t BinaryenShimCountInstructions(BinaryenModuleRef module) {
+  return abortOnFatal([&]() -> size_t {
+    struct Counter : public PostWalker<Counter, UnifiedExpressionVisitor<Counter>> {
+      size_t count = 0;
+      void visitExpression(Expression* curr) { count++; }
+    };
+
+    Counter counter;
+    for (auto& func : ((Module*)module)->functions) {
+      if (!func->imported()) {
+        counter.walk(func->body);
+      }
+    }
+    return counter.count;
+  });
+}
+
+// Returns a copy of the module that shares nothing with the original.
+extern "C" BinaryenModuleRef BinaryenShimModuleCopy(BinaryenModuleRef module) {
+  return abortOnFatal([&]() -> BinaryenModuleRef {
+    auto* copy = new Module;
+    ModuleUtils::copyModule(*(Module*)module, *copy);
+    return copy;
+  });
+}
+
+// Returns the number of imports of all kinds, the length of the arrays
+// BinaryenShimGetImports fills.
+extern "C" BinaryenIndex BinaryenShimGetNumImports(BinaryenModuleRef module) {
+  return abortOnFatal([&]() -> BinaryenIndex {
+    auto* wasm = (Module*)module;
+    BinaryenIndex count = 0;
+    ModuleUtils::iterImportedFunctions(*wasm, [&](Function*) { count++; });
+    ModuleUtils::iterImportedTables(*wasm, [&](Table*) { count++; });
+    ModuleUtils::iterImportedMemories(*wasm, [&](Memory*) { count++; });
+    ModuleUtils::iterImportedGlobals(*wasm, [&](Global*) { count++; });
+    ModuleUtils::iterImportedTags(*wasm, [&](Tag*) { count++; });
+    return count;
+  });
+}
+
+// Fills the arrays, of BinaryenShimGetNumImports entries each, with the kind,
+// module and base name of all imports in a single walk over the module, in the
+// order functions, tables, memories, globals, tags. The names are owned by
+// Binaryen.
+extern "C" void BinaryenShimGetImports(BinaryenModuleRef module,
+                                       BinaryenExternalKind* kinds,
+                                       const char** modules,
+                                       const char** bases) {
+  abortOnFatal([&]() {
+    auto* wasm = (Module*)module;
+    BinaryenIndex index = 0;
+    auto add = [&](ExternalKind kind, Importable* import) {
+      kinds[index] = BinaryenExternalKind(kind);
+      modules[index] = import->module.str.data();
+      bases[index] = import->base.str.data();
+      index++;
+    };
+    ModuleUtils::iterImportedFunctions(
+      *wasm, [&](Function* func) { add(ExternalKind::Function, func); });
+    ModuleUtils::iterImportedTables(
+      *wasm, [&](Table* table) { add(ExternalKind::Table, table); });
+    ModuleUtils::iterImportedMemories(
+      *wasm, [&](Memory* memory) { add(ExternalKind::Memory, memory); });
+    ModuleUtils::iterImportedGlobals(
+      *wasm, [&](Global* global) { add(ExternalKind::Global, global); });
+    ModuleUtils::iterImportedTags(
+      *wasm, [&](Tag* tag) { add(ExternalKind::Tag, tag); });
+  });
+}
+
+// Returns the name of the function the element with the given index of the
+// segment refers to, or NULL if the element is not a `ref.func`, e.g. a
+// `ref.null`. Unlike BinaryenElementSegmentGetData, other elements don't make
+// Binaryen hit a fatal error. The name is owned by Binaryen.
+extern "C" const char*
+BinaryenShimElementSegmentGetFunction(BinaryenElementSegmentRef elem, BinaryenIndex index) {
+  return abortOnFatal([&]() -> const char* {
+    auto* refFunc = ((ElementSegment*)elem)->data.at(index)->dynCast<RefFunc>();
+    if (!refFunc) {
+      return NULL;
+    }
+    return refFunc->func.str.data();
+  });
+}
+
+// Heap type access by the index the types get in the binary format.
+
+extern "C" BinaryenIndex BinaryenShimGetNumHeapTypes(BinaryenModuleRef module) {
+  return abortOnFatal([&]() -> BinaryenIndex {
+    return ModuleUtils::getOptimizedIndexedHeapTypes(*(Module*)module).types.size();
+  });
+}
+
+// Fills `heapTypes`, which must have room for BinaryenShimGetNumHeapTypes
+// entries, with all heap types of the module in index order. Collecting the
+// types walks the whole module, so they are handed out in one call.
+extern "C" void
+BinaryenShimGetHeapTypes(BinaryenModuleRef module, BinaryenHeapType* heapTypes) {
+  return abortOnFatal([&] {
+    auto types = ModuleUtils::getOptimizedIndexedHeapTypes(*(Module*)module).types;
+    for (size_t i = 0; i < types.size(); i++) {
+      heapTypes[i] = types[i].getID();
+    }
+  });
+}
+
+// Returns NULL if the type has no name. The name is owned by Binaryen.
+extern "C" const char*
+BinaryenShimGetHeapTypeName(BinaryenModuleRef module, BinaryenHeapType heapType) {
+  auto& typeNames = ((Module*)module)->typeNames;
+  auto it = typeNames.find(HeapType(heapType));
+  if (it == typeNames.end() || !it->second.name) {
+    return NULL;
+  }
+  return it->second.name.str.data();
+}
+
+// Whether the module had a target_features section when it was read, or is
+// otherwise set up to emit one.
+extern "C" int BinaryenShimHasFeaturesSection(BinaryenModuleRef module) {
+  return ((Module*)module)->hasFeaturesSection ? 1 : 0;
+}
+
+// Prints the expression in the text format. The result should be released with
+// BinaryenShimDisposeString.
+extern "C" char* BinaryenShimExpressionToText(BinaryenExpressionRef expr) {
+  return abortOnFatal([&]() -> char* {
+    ostringstream text;
+    {
+      ScopedNoColors noColors;
+      text << *(Expression*)expr;
+    }
+    return copyToMallocedString(text.str());
+  });
 }
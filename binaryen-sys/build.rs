@@ -68,10 +68,20 @@ fn gen_passes() {
         })
         .collect();
 
+    let names: Vec<String> = passes
+        .iter()
+        .map(|pass| format!(r#""{}""#, pass.name.to_string()))
+        .collect();
+
     let output = format!(
         r#"
         use std::str::FromStr;
 
+        /// Names of all the passes registered in Binaryen.
+        pub const PASS_NAMES: &[&str] = &[
+            {names}
+        ];
+
         #[derive(Eq, PartialEq, Debug)]
         pub enum OptimizationPass {{
             {ids}
@@ -108,6 +118,14 @@ fn gen_passes() {
                 assert_eq!(OptimizationPass::{test_id}, OptimizationPass::from_str("{test_name}").expect("from_str expected to work"));
             }}
 
+            #[test]
+            fn test_pass_names() {{
+                assert!(PASS_NAMES.contains(&"{test_name}"));
+                for name in PASS_NAMES {{
+                    assert!(OptimizationPass::from_str(name).is_ok());
+                }}
+            }}
+
             #[test]
             fn test_description() {{
                 assert_eq!(OptimizationPass::{test_id}.description(), "{test_description}");
@@ -117,6 +135,7 @@ fn gen_passes() {
         ids = ids.join(",\n"),
         fromstrs = fromstrs.join(",\n"),
         descriptions = descriptions.join(",\n"),
+        names = names.join(",\n"),
         test_id = passes[0].id.to_string(),
         test_name = passes[0].name.to_string(),
         test_description = passes[0].description.to_string()
@@ -131,17 +150,45 @@ fn main() {
         panic!("binaryen submodule not found. Please run `git submodule update --init` first.");
     }
 
+    // Once any `rerun-if-changed` is emitted cargo stops rerunning the build script on every
+    // change in the package, so list everything the build depends on.
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=Shim.cpp");
+    println!("cargo:rerun-if-changed=wrapper.h");
+    println!("cargo:rerun-if-changed=binaryen/CMakeLists.txt");
+    println!("cargo:rerun-if-changed=binaryen/src");
+
     gen_passes();
 
+    // With the `dynamic` feature Binaryen is built as a shared library and linked dynamically.
+    let dynamic = env::var_os("CARGO_FEATURE_DYNAMIC").is_some();
+
     let dst = cmake::Config::new("binaryen")
-        .define("BUILD_STATIC_LIB", "ON")
+        .define("BUILD_STATIC_LIB", if dynamic { "OFF" } else { "ON" })
         .define("ENABLE_WERROR", "OFF")
         .define("BUILD_TESTS", "OFF")
         .define("BUILD_TOOLS", "OFF")
+        // Make fatal errors throw instead of exiting the process, so that the shim can turn
+        // them into errors. Every shim entry point that can hit one catches it, as it must not
+        // unwind into Rust. Internal assertions still abort.
+        .define("THROW_ON_FATAL", "ON")
         .build();
 
-    println!("cargo:rustc-link-search=native={}/build/lib", dst.display());
-    println!("cargo:rustc-link-lib=static=binaryen");
+    let lib_dir = dst.join("build/lib");
+    println!("cargo:rustc-link-search=native={}", lib_dir.display());
+    if dynamic {
+        println!("cargo:rustc-link-lib=dylib=binaryen");
+        // Let this crate's own tests find the library at runtime. Link arguments only apply
+        // to this crate's targets, so dependents add the search path themselves from
+        // `DEP_BINARYEN_LIB_DIR`, set by `cargo:lib_dir` below, like the build script of the
+        // `binaryen` crate does.
+        if !env::var("TARGET").unwrap().contains("windows") {
+            println!("cargo:rustc-link-arg=-Wl,-rpath,{}", lib_dir.display());
+        }
+        println!("cargo:lib_dir={}", lib_dir.display());
+    } else {
+        println!("cargo:rustc-link-lib=static=binaryen");
+    }
 
     // We need to link against C++ std lib
     if let Some(cpp_stdlib) = get_cpp_stdlib() {
@@ -165,6 +212,8 @@ fn main() {
             "binaryen/src/tools/fuzzing/random.cpp",
         ])
         .include("binaryen/src")
+        // Must agree with the Binaryen build above, as `Fatal` is defined in a header.
+        .define("THROW_ON_FATAL", None)
         .cpp_link_stdlib(None)
         .warnings(false)
         .cpp(true)
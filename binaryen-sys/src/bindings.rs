@@ -6338,6 +6338,12 @@ extern "C" {
         inputSize: usize,
     ) -> BinaryenModuleRef;
 }
+extern "C" {
+    pub fn BinaryenModuleSafeParse(
+        text: *const ::std::os::raw::c_char,
+        error: *mut *mut ::std::os::raw::c_char,
+    ) -> BinaryenModuleRef;
+}
 extern "C" {
     pub fn translateToFuzz(
         data: *const ::std::os::raw::c_char,
@@ -6345,22 +6351,182 @@ extern "C" {
         emitAtomics: bool,
     ) -> BinaryenModuleRef;
 }
+extern "C" {
+    pub fn BinaryenShimTranslateToFuzz(
+        data: *const ::std::os::raw::c_char,
+        len: usize,
+        features: BinaryenFeatures,
+    ) -> BinaryenModuleRef;
+}
 extern "C" {
     pub fn BinaryenShimDisposeBinaryenModuleAllocateAndWriteResult(
         result: BinaryenModuleAllocateAndWriteResult,
     );
 }
+extern "C" {
+    pub fn BinaryenModuleAllocateAndWriteWithSettings(
+        module: BinaryenModuleRef,
+        sourceMapUrl: *const ::std::os::raw::c_char,
+        debugInfo: ::std::os::raw::c_int,
+        error: *mut *mut ::std::os::raw::c_char,
+    ) -> BinaryenModuleAllocateAndWriteResult;
+}
+extern "C" {
+    pub fn BinaryenModuleSetFunctionName(
+        module: BinaryenModuleRef,
+        index: BinaryenIndex,
+        name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
 extern "C" {
     pub fn BinaryenModuleRunPassesWithSettings(
         module: BinaryenModuleRef,
         passes: *mut *const ::std::os::raw::c_char,
         numPasses: BinaryenIndex,
+        argumentNames: *mut *const ::std::os::raw::c_char,
+        argumentValues: *mut *const ::std::os::raw::c_char,
+        numArguments: BinaryenIndex,
         shrinkLevel: ::std::os::raw::c_int,
         optimizeLevel: ::std::os::raw::c_int,
         debugInfo: ::std::os::raw::c_int,
-    );
+        zeroFilledMemory: ::std::os::raw::c_int,
+        alwaysInlineMaxSize: ::std::os::raw::c_int,
+        flexibleInlineMaxSize: ::std::os::raw::c_int,
+        oneCallerInlineMaxSize: ::std::os::raw::c_int,
+        closedWorld: ::std::os::raw::c_int,
+    ) -> *mut ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn BinaryenShimFunctionRunPassesWithSettings(
+        func: BinaryenFunctionRef,
+        module: BinaryenModuleRef,
+        passes: *mut *const ::std::os::raw::c_char,
+        numPasses: BinaryenIndex,
+        shrinkLevel: ::std::os::raw::c_int,
+        optimizeLevel: ::std::os::raw::c_int,
+        debugInfo: ::std::os::raw::c_int,
+        zeroFilledMemory: ::std::os::raw::c_int,
+        alwaysInlineMaxSize: ::std::os::raw::c_int,
+        flexibleInlineMaxSize: ::std::os::raw::c_int,
+        oneCallerInlineMaxSize: ::std::os::raw::c_int,
+        closedWorld: ::std::os::raw::c_int,
+    ) -> *mut ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn BinaryenShimRunPassCapturingOutput(
+        module: BinaryenModuleRef,
+        pass: *const ::std::os::raw::c_char,
+        argumentNames: *mut *const ::std::os::raw::c_char,
+        argumentValues: *mut *const ::std::os::raw::c_char,
+        numArguments: BinaryenIndex,
+        error: *mut *mut ::std::os::raw::c_char,
+    ) -> *mut ::std::os::raw::c_char;
 }
 extern "C" {
     pub fn BinaryenModuleSafeValidate(module: BinaryenModuleRef) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn BinaryenModuleSafeValidateWithFeaturesAndMessage(
+        module: BinaryenModuleRef,
+        enabled: BinaryenFeatures,
+    ) -> *mut ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn BinaryenModuleSafeValidateWithMessage(
+        module: BinaryenModuleRef,
+    ) -> *mut ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn BinaryenShimDisposeString(str_: *mut ::std::os::raw::c_char);
+}
+extern "C" {
+    pub fn BinaryenModuleSafeValidateWithFeatures(
+        module: BinaryenModuleRef,
+        features: BinaryenFeatures,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn BinaryenModuleMerge(
+        module: BinaryenModuleRef,
+        otherModule: BinaryenModuleRef,
+        exportClash: *mut ::std::os::raw::c_int,
+    ) -> *mut ::std::os::raw::c_char;
+}
+pub type BinaryenShimDiagnosticCallback =
+    ::std::option::Option<unsafe extern "C" fn(message: *const ::std::os::raw::c_char, len: usize)>;
+extern "C" {
+    pub fn BinaryenShimSetDiagnosticCallback(callback: BinaryenShimDiagnosticCallback);
+}
+extern "C" {
+    pub fn BinaryenShimGetNumRegisteredPasses() -> BinaryenIndex;
+}
+extern "C" {
+    pub fn BinaryenShimGetRegisteredPassName(index: BinaryenIndex) -> *mut ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn BinaryenShimIsPassRegistered(
+        name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn BinaryenShimIsPassHidden(name: *const ::std::os::raw::c_char) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn BinaryenShimIsPassFunctionParallel(
+        name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn BinaryenShimGetPassDescription(
+        name: *const ::std::os::raw::c_char,
+    ) -> *mut ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn BinaryenShimLimitDataSegments(
+        module: BinaryenModuleRef,
+        max: BinaryenIndex,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn BinaryenShimCountInstructions(module: BinaryenModuleRef) -> usize;
+}
+extern "C" {
+    pub fn BinaryenShimModuleCopy(module: BinaryenModuleRef) -> BinaryenModuleRef;
+}
+extern "C" {
+    pub fn BinaryenShimGetNumImports(module: BinaryenModuleRef) -> BinaryenIndex;
+}
+extern "C" {
+    pub fn BinaryenShimGetImports(
+        module: BinaryenModuleRef,
+        kinds: *mut BinaryenExternalKind,
+        modules: *mut *const ::std::os::raw::c_char,
+        bases: *mut *const ::std::os::raw::c_char,
+    );
+}
+extern "C" {
+    pub fn BinaryenShimElementSegmentGetFunction(
+        elem: BinaryenElementSegmentRef,
+        index: BinaryenIndex,
+    ) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn BinaryenShimGetNumHeapTypes(module: BinaryenModuleRef) -> BinaryenIndex;
+}
+extern "C" {
+    pub fn BinaryenShimGetHeapTypes(module: BinaryenModuleRef, heapTypes: *mut BinaryenHeapType);
+}
+extern "C" {
+    pub fn BinaryenShimGetHeapTypeName(
+        module: BinaryenModuleRef,
+        heapType: BinaryenHeapType,
+    ) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn BinaryenShimHasFeaturesSection(module: BinaryenModuleRef) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn BinaryenShimExpressionToText(expr: BinaryenExpressionRef)
+        -> *mut ::std::os::raw::c_char;
+}
 pub type __builtin_va_list = *mut ::std::os::raw::c_char;
@@ -8,19 +8,106 @@ extern "C" {
 
 BinaryenModuleRef BinaryenModuleSafeRead(const char* input, size_t inputSize);
 
+BinaryenModuleRef BinaryenModuleSafeParse(const char* text, char** error);
+
 BinaryenModuleRef translateToFuzz(const char *data, size_t len, bool emitAtomics);
 
+BinaryenModuleRef BinaryenShimTranslateToFuzz(
+    const char* data, size_t len, BinaryenFeatures features
+);
+
 void BinaryenShimDisposeBinaryenModuleAllocateAndWriteResult(
     BinaryenModuleAllocateAndWriteResult result
 );
 
-void BinaryenModuleRunPassesWithSettings(
+BinaryenModuleAllocateAndWriteResult BinaryenModuleAllocateAndWriteWithSettings(
+    BinaryenModuleRef module, const char* sourceMapUrl, int debugInfo, char** error
+);
+
+int BinaryenModuleSetFunctionName(
+    BinaryenModuleRef module, BinaryenIndex index, const char* name
+);
+
+char* BinaryenModuleRunPassesWithSettings(
     BinaryenModuleRef module, const char** passes, BinaryenIndex numPasses,
-    int shrinkLevel, int optimizeLevel, int debugInfo
+    const char** argumentNames, const char** argumentValues,
+    BinaryenIndex numArguments, int shrinkLevel, int optimizeLevel, int debugInfo,
+    int zeroFilledMemory, int alwaysInlineMaxSize, int flexibleInlineMaxSize,
+    int oneCallerInlineMaxSize, int closedWorld
+);
+
+char* BinaryenShimFunctionRunPassesWithSettings(
+    BinaryenFunctionRef func, BinaryenModuleRef module, const char** passes,
+    BinaryenIndex numPasses, int shrinkLevel, int optimizeLevel, int debugInfo,
+    int zeroFilledMemory, int alwaysInlineMaxSize, int flexibleInlineMaxSize,
+    int oneCallerInlineMaxSize, int closedWorld
+);
+
+char* BinaryenShimRunPassCapturingOutput(
+    BinaryenModuleRef module, const char* pass, const char** argumentNames,
+    const char** argumentValues, BinaryenIndex numArguments, char** error
 );
 
 int BinaryenModuleSafeValidate(BinaryenModuleRef module);
 
+char* BinaryenModuleSafeValidateWithFeaturesAndMessage(
+    BinaryenModuleRef module, BinaryenFeatures enabled
+);
+
+char* BinaryenModuleSafeValidateWithMessage(BinaryenModuleRef module);
+
+void BinaryenShimDisposeString(char* str);
+
+int BinaryenModuleSafeValidateWithFeatures(
+    BinaryenModuleRef module, BinaryenFeatures features
+);
+
+char* BinaryenModuleMerge(
+    BinaryenModuleRef module, BinaryenModuleRef otherModule, int* exportClash
+);
+
+typedef void (*BinaryenShimDiagnosticCallback)(const char* message, size_t len);
+
+void BinaryenShimSetDiagnosticCallback(BinaryenShimDiagnosticCallback callback);
+
+BinaryenIndex BinaryenShimGetNumRegisteredPasses();
+
+char* BinaryenShimGetRegisteredPassName(BinaryenIndex index);
+
+int BinaryenShimIsPassRegistered(const char* name);
+
+int BinaryenShimIsPassHidden(const char* name);
+
+int BinaryenShimIsPassFunctionParallel(const char* name);
+
+char* BinaryenShimGetPassDescription(const char* name);
+
+int BinaryenShimLimitDataSegments(BinaryenModuleRef module, BinaryenIndex max);
+
+size_t BinaryenShimCountInstructions(BinaryenModuleRef module);
+
+BinaryenModuleRef BinaryenShimModuleCopy(BinaryenModuleRef module);
+
+BinaryenIndex BinaryenShimGetNumImports(BinaryenModuleRef module);
+
+void BinaryenShimGetImports(BinaryenModuleRef module, BinaryenExternalKind* kinds,
+                            const char** modules, const char** bases);
+
+const char* BinaryenShimElementSegmentGetFunction(
+    BinaryenElementSegmentRef elem, BinaryenIndex index
+);
+
+BinaryenIndex BinaryenShimGetNumHeapTypes(BinaryenModuleRef module);
+
+void BinaryenShimGetHeapTypes(BinaryenModuleRef module, BinaryenHeapType* heapTypes);
+
+const char*
+BinaryenShimGetHeapTypeName(BinaryenModuleRef module, BinaryenHeapType heapType);
+
+int BinaryenShimHasFeaturesSection(BinaryenModuleRef module);
+
+char* BinaryenShimExpressionToText(BinaryenExpressionRef expr);
+
 #ifdef __cplusplus
 }
 #endif
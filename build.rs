@@ -0,0 +1,18 @@
+use std::env;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=DEP_BINARYEN_LIB_DIR");
+
+    // With the `dynamic` feature binaryen-sys reports where the shared library is, see its
+    // build script. Add it to the runtime search path of this crate's tests and examples, which
+    // the link arguments of binaryen-sys don't apply to.
+    if let Some(lib_dir) = env::var_os("DEP_BINARYEN_LIB_DIR") {
+        if !env::var("TARGET").unwrap().contains("windows") {
+            println!(
+                "cargo:rustc-link-arg=-Wl,-rpath,{}",
+                lib_dir.to_string_lossy()
+            );
+        }
+    }
+}
@@ -1,8 +1,7 @@
 extern crate binaryen;
 
 use std::env;
-use std::fs::File;
-use std::io::prelude::*;
+use std::path::Path;
 use std::process;
 
 struct Args {
@@ -17,44 +16,18 @@ fn parse_args() -> Result<Args, ()> {
         return Err(());
     }
 
-    let codegen_config = match &*args[1] {
-        "-O0" => binaryen::CodegenConfig {
-            optimization_level: 0,
-            shrink_level: 0,
-            debug_info: true,
-        },
-        "-O1" => binaryen::CodegenConfig {
-            optimization_level: 1,
-            shrink_level: 0,
-            debug_info: true,
-        },
-        "-O2" => binaryen::CodegenConfig {
-            optimization_level: 2,
-            shrink_level: 0,
-            debug_info: true,
-        },
-        "-O3" => binaryen::CodegenConfig {
-            optimization_level: 3,
-            shrink_level: 0,
-            debug_info: true,
-        },
-        "-O4" => binaryen::CodegenConfig {
-            optimization_level: 4,
-            shrink_level: 0,
-            debug_info: true,
-        },
-        "-Os" => binaryen::CodegenConfig {
-            optimization_level: 2,
-            shrink_level: 1,
-            debug_info: true,
-        },
-        "-Oz" => binaryen::CodegenConfig {
-            optimization_level: 2,
-            shrink_level: 2,
-            debug_info: true,
-        },
+    let opt_level = match &*args[1] {
+        "-O0" => binaryen::OptLevel::O0,
+        "-O1" => binaryen::OptLevel::O1,
+        "-O2" => binaryen::OptLevel::O2,
+        "-O3" => binaryen::OptLevel::O3,
+        "-O4" => binaryen::OptLevel::O4,
+        "-Os" => binaryen::OptLevel::Os,
+        "-Oz" => binaryen::OptLevel::Oz,
         _ => return Err(()),
     };
+    let mut codegen_config = opt_level.to_codegen_config();
+    codegen_config.debug_info = true;
     let input_path = args[2].clone();
     let output_path = args[3].clone();
 
@@ -65,23 +38,9 @@ fn parse_args() -> Result<Args, ()> {
     })
 }
 
-fn read_module(filename: &str) -> binaryen::Module {
-    let mut f = File::open(filename).expect("file not found");
-    let mut contents = Vec::new();
-    f.read_to_end(&mut contents)
-        .expect("something went wrong reading the file");
+const USAGE: &'static str = r#"usage: wasm_opt OPT_LEVEL INPUT OUTPUT
 
-    binaryen::Module::read(&contents).expect("something went wrong parsing the file")
-}
-
-fn write_module(filename: &str, wasm: &[u8]) {
-    let mut f = File::create(filename).expect("failed to create output");
-    f.write_all(wasm).expect("failed to write file");
-}
-
-const USAGE: &'static str = r#"usage: wasm_opt OPT_LEVEL FILENAME
-
-OPT_LEVEL - one of -O0, -O1, -O2, -O3, -Os, -Oz
+OPT_LEVEL - one of -O0, -O1, -O2, -O3, -O4, -Os, -Oz
 INPUT     - path to a wasm module to optimize
 OUTPUT    - path to write the optimized module
 "#;
@@ -94,9 +53,12 @@ fn main() {
             process::exit(1);
         }
     };
-    let mut module = read_module(&args.input_path);
-    module.optimize(&args.codegen_config);
-
-    let optimized_wasm = module.write();
-    write_module(&args.output_path, &optimized_wasm);
+    if let Err(err) = binaryen::optimize_file(
+        Path::new(&args.input_path),
+        Path::new(&args.output_path),
+        &args.codegen_config,
+    ) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
 }
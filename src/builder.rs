@@ -0,0 +1,110 @@
+use crate::{Expr, Features, Function, Module, Segment, Ty, ValidationError, ValueTy};
+use std::ops::Deref;
+
+/// Builds a module from scratch and checks it before handing it out.
+///
+/// The expression builders of the module under construction are available through `Deref`,
+/// while adding functions, globals and memory goes through the builder. [`ModuleBuilder::build`]
+/// inserts the missing `drop`s and validates the result, so an invalid module can't be shipped
+/// by accident.
+///
+/// ```
+/// # use binaryen::{ModuleBuilder, Ty, ValueTy};
+/// let mut builder = ModuleBuilder::new();
+/// let body = builder.i32_const(42);
+/// builder.add_fn("answer", Ty::none(), Ty::value(ValueTy::I32), &[], body);
+/// let module = builder.build().unwrap();
+/// assert!(module.get_function("answer").is_some());
+/// ```
+#[derive(Default)]
+pub struct ModuleBuilder {
+    module: Module,
+}
+
+impl ModuleBuilder {
+    /// Start building an empty module.
+    pub fn new() -> ModuleBuilder {
+        ModuleBuilder {
+            module: Module::new(),
+        }
+    }
+
+    /// Add a function to the module, see [`Module::add_fn`].
+    pub fn add_fn(
+        &mut self,
+        name: &str,
+        params: Ty,
+        results: Ty,
+        var_tys: &[ValueTy],
+        body: Expr,
+    ) -> Function<'_> {
+        self.module.add_fn(name, params, results, var_tys, body)
+    }
+
+    /// Add a global to the module, see [`Module::add_global`].
+    pub fn add_global(&mut self, name: &str, ty: ValueTy, mutable: bool, init: Expr) {
+        self.module.add_global(name, ty, mutable, init)
+    }
+
+    /// Set the memory of the module, see [`Module::set_memory`].
+    pub fn set_memory(
+        &mut self,
+        initial: u32,
+        maximum: Option<u32>,
+        export_name: Option<&str>,
+        segments: Vec<Segment>,
+        shared: bool,
+    ) {
+        self.module
+            .set_memory(initial, maximum, export_name, segments, shared)
+    }
+
+    /// Set the features enabled on the module, see [`Module::set_features`].
+    pub fn set_features(&mut self, features: Features) {
+        self.module.set_features(features)
+    }
+
+    /// Finish the module.
+    ///
+    /// Values that are left unused in blocks get dropped, so e.g. a `call` returning a value can
+    /// be used as a statement. Then the module is validated, and returned only if it's valid.
+    pub fn build(self) -> Result<Module, ValidationError> {
+        unsafe { binaryen_sys::BinaryenModuleAutoDrop(self.module.inner.raw) };
+        self.module.validate()?;
+        Ok(self.module)
+    }
+}
+
+impl Deref for ModuleBuilder {
+    type Target = Module;
+
+    fn deref(&self) -> &Module {
+        &self.module
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_auto_drops() {
+        let mut builder = ModuleBuilder::new();
+        let value = builder.i32_const(1);
+        let body = builder.block(None, vec![value, builder.nop()], None);
+        builder.add_fn("main", Ty::none(), Ty::none(), &[], body);
+
+        let module = builder.build().unwrap();
+        let body = module.get_function("main").unwrap().body().to_text();
+        assert!(body.contains("(drop"), "{}", body);
+    }
+
+    #[test]
+    fn test_build_invalid() {
+        let mut builder = ModuleBuilder::new();
+        let body = builder.call("missing", vec![], Ty::none());
+        builder.add_fn("main", Ty::none(), Ty::none(), &[], body);
+
+        assert!(builder.build().is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{Module, ReadError, WASM_PREAMBLE};
+use std::{error, fmt, io};
+
+/// The magic bytes every gzip stream starts with.
+const GZIP_MAGIC: &[u8; 2] = b"\x1f\x8b";
+
+/// How a wasm binary is compressed, see [`Module::read_compressed`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// The binary isn't compressed.
+    None,
+    /// A gzip stream, e.g. a `.wasm.gz` file. Needs the `flate2` feature.
+    Gzip,
+    /// A brotli stream, e.g. a `.wasm.br` file. Needs the `brotli` feature.
+    Brotli,
+    /// Detect the compression from the first bytes.
+    ///
+    /// The wasm and gzip magic numbers are recognized. Brotli streams have no magic number, so
+    /// anything else is assumed to be brotli if the `brotli` feature is enabled, and
+    /// uncompressed otherwise.
+    Auto,
+}
+
+impl Compression {
+    fn detect(bytes: &[u8]) -> Compression {
+        if bytes.starts_with(&WASM_PREAMBLE[..4]) {
+            Compression::None
+        } else if bytes.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if cfg!(feature = "brotli") {
+            Compression::Brotli
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Error returned by [`Module::read_compressed`].
+#[derive(Debug)]
+pub enum ReadCompressedError {
+    /// Support for this compression is not enabled, see [`Compression`] for the features.
+    Unsupported(Compression),
+    /// The compressed stream is malformed.
+    Decompress(io::Error),
+    /// The decompressed bytes are not a valid module.
+    Read(ReadError),
+}
+
+impl fmt::Display for ReadCompressedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadCompressedError::Unsupported(compression) => {
+                write!(f, "unsupported compression: {:?}", compression)
+            }
+            ReadCompressedError::Decompress(err) => write!(f, "failed to decompress: {}", err),
+            ReadCompressedError::Read(err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for ReadCompressedError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ReadCompressedError::Unsupported(_) => None,
+            ReadCompressedError::Decompress(err) => Some(err),
+            ReadCompressedError::Read(err) => Some(err),
+        }
+    }
+}
+
+impl From<ReadError> for ReadCompressedError {
+    fn from(err: ReadError) -> ReadCompressedError {
+        ReadCompressedError::Read(err)
+    }
+}
+
+#[cfg(feature = "flate2")]
+fn decompress_gzip(bytes: &[u8]) -> Result<Vec<u8>, ReadCompressedError> {
+    use std::io::Read;
+
+    let mut decompressed = vec![];
+    flate2::read::GzDecoder::new(bytes)
+        .read_to_end(&mut decompressed)
+        .map_err(ReadCompressedError::Decompress)?;
+    Ok(decompressed)
+}
+
+#[cfg(not(feature = "flate2"))]
+fn decompress_gzip(_bytes: &[u8]) -> Result<Vec<u8>, ReadCompressedError> {
+    Err(ReadCompressedError::Unsupported(Compression::Gzip))
+}
+
+#[cfg(feature = "brotli")]
+fn decompress_brotli(bytes: &[u8]) -> Result<Vec<u8>, ReadCompressedError> {
+    use std::io::Read;
+
+    let mut decompressed = vec![];
+    brotli::Decompressor::new(bytes, 4096)
+        .read_to_end(&mut decompressed)
+        .map_err(ReadCompressedError::Decompress)?;
+    Ok(decompressed)
+}
+
+#[cfg(not(feature = "brotli"))]
+fn decompress_brotli(_bytes: &[u8]) -> Result<Vec<u8>, ReadCompressedError> {
+    Err(ReadCompressedError::Unsupported(Compression::Brotli))
+}
+
+impl Module {
+    /// Deserialize a module from a possibly compressed binary wasm, see [`Module::read`].
+    pub fn read_compressed(
+        bytes: &[u8],
+        compression: Compression,
+    ) -> Result<Module, ReadCompressedError> {
+        let compression = match compression {
+            Compression::Auto => Compression::detect(bytes),
+            compression => compression,
+        };
+        let decompressed = match compression {
+            Compression::None | Compression::Auto => return Ok(Module::read(bytes)?),
+            Compression::Gzip => decompress_gzip(bytes)?,
+            Compression::Brotli => decompress_brotli(bytes)?,
+        };
+        Ok(Module::read(&decompressed)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &'static str = r#"(module (func (export "main") (nop)))"#;
+
+    #[test]
+    fn test_read_uncompressed() {
+        let input = wat::parse_str(CODE).unwrap();
+        for compression in [Compression::None, Compression::Auto] {
+            let module = Module::read_compressed(&input, compression).unwrap();
+            module.assert_valid();
+        }
+        assert!(matches!(
+            Module::read_compressed(b"\0asm", Compression::None).err(),
+            Some(ReadCompressedError::Read(ReadError::TooShort))
+        ));
+    }
+
+    #[cfg(not(feature = "flate2"))]
+    #[test]
+    fn test_gzip_unsupported() {
+        assert!(matches!(
+            Module::read_compressed(b"\x1f\x8b\x08\0", Compression::Auto).err(),
+            Some(ReadCompressedError::Unsupported(Compression::Gzip))
+        ));
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_read_gzip() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&wat::parse_str(CODE).unwrap()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        for compression in [Compression::Gzip, Compression::Auto] {
+            let module = Module::read_compressed(&compressed, compression).unwrap();
+            module.assert_valid();
+        }
+        assert!(matches!(
+            Module::read_compressed(&compressed[..8], Compression::Gzip).err(),
+            Some(ReadCompressedError::Decompress(_))
+        ));
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn test_read_brotli() {
+        use std::io::Write;
+
+        let mut compressed = vec![];
+        {
+            let mut encoder = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+            encoder.write_all(&wat::parse_str(CODE).unwrap()).unwrap();
+        }
+
+        for compression in [Compression::Brotli, Compression::Auto] {
+            let module = Module::read_compressed(&compressed, compression).unwrap();
+            module.assert_valid();
+        }
+    }
+}
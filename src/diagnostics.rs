@@ -0,0 +1,90 @@
+use std::os::raw::c_char;
+use std::sync::Mutex;
+use std::{panic, slice};
+
+type DiagnosticHandler = Box<dyn Fn(&str) + Send + Sync>;
+
+static HANDLER: Mutex<Option<DiagnosticHandler>> = Mutex::new(None);
+
+/// Route the diagnostics Binaryen prints to stderr to `handler` instead.
+///
+/// The handler is called once per line, without the trailing newline. It's process-wide and
+/// replaces any previously set handler. Passes may run on several threads, so the handler can
+/// be called from threads other than the one running the passes.
+///
+/// The handler must not call back into Binaryen. If it panics, the panic is swallowed, since it
+/// can't unwind through Binaryen.
+///
+/// Output captured by the crate itself, like the diagnostics returned by
+/// [`Module::validate`](crate::Module::validate), doesn't reach the handler. Such captures
+/// only take what the capturing thread prints, so they don't hold back the diagnostics of
+/// other threads from the handler.
+pub fn set_diagnostic_handler(handler: Box<dyn Fn(&str) + Send + Sync>) {
+    *HANDLER.lock().unwrap_or_else(|err| err.into_inner()) = Some(handler);
+    unsafe { binaryen_sys::BinaryenShimSetDiagnosticCallback(Some(forward_diagnostic)) }
+}
+
+/// Remove the handler set by [`set_diagnostic_handler`], making Binaryen print diagnostics to
+/// stderr again.
+///
+/// The handler is not called anymore once this returns, even by passes still running on other
+/// threads.
+pub fn reset_diagnostic_handler() {
+    unsafe { binaryen_sys::BinaryenShimSetDiagnosticCallback(None) }
+    *HANDLER.lock().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+unsafe extern "C" fn forward_diagnostic(message: *const c_char, len: usize) {
+    let message = if len == 0 {
+        String::new()
+    } else {
+        String::from_utf8_lossy(slice::from_raw_parts(message as *const u8, len)).into_owned()
+    };
+    let _ = panic::catch_unwind(|| {
+        if let Some(handler) = &*HANDLER.lock().unwrap_or_else(|err| err.into_inner()) {
+            handler(&message);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Module;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_diagnostic_handler() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = lines.clone();
+        set_diagnostic_handler(Box::new(move |line| {
+            sink.lock().unwrap().push(line.to_string())
+        }));
+
+        // An invalid module makes the validator print its errors to stderr.
+        let module = Module::new();
+        unsafe {
+            let raw = module.inner.raw;
+            let body = binaryen_sys::BinaryenConst(raw, binaryen_sys::BinaryenLiteralInt64(0));
+            let name = std::ffi::CString::new("test").unwrap();
+            binaryen_sys::BinaryenAddFunction(
+                raw,
+                name.as_ptr(),
+                binaryen_sys::BinaryenTypeNone(),
+                binaryen_sys::BinaryenTypeInt32(),
+                std::ptr::null_mut(),
+                0,
+                body,
+            );
+        }
+        assert!(!module.is_valid());
+
+        reset_diagnostic_handler();
+        let lines = lines.lock().unwrap();
+        assert!(
+            lines.iter().any(|line| line.contains("test")),
+            "{:?}",
+            lines
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Helpers for producing and consuming bits of the wasm binary format on the Rust side.
+
+/// Append `value` encoded as unsigned LEB128.
+pub(crate) fn write_leb128_u32(buf: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+/// Decode an unsigned LEB128 value from the start of `buf`.
+///
+/// Returns the value and the number of bytes it took, or `None` if `buf` ends early or the
+/// value doesn't fit into `u32`.
+pub(crate) fn read_leb128_u32(buf: &[u8]) -> Option<(u32, usize)> {
+    let mut value = 0u32;
+    for (i, &byte) in buf.iter().enumerate().take(5) {
+        let bits = (byte & 0x7f) as u32;
+        if i == 4 && bits > 0x0f {
+            return None;
+        }
+        value |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Append a length-prefixed string, as used for names in the binary format.
+pub(crate) fn write_name(buf: &mut Vec<u8>, name: &[u8]) {
+    write_leb128_u32(buf, name.len() as u32);
+    buf.extend_from_slice(name);
+}
+
+/// Append a custom section with the given name and payload to a serialized module.
+pub(crate) fn append_custom_section(binary: &mut Vec<u8>, name: &str, payload: &[u8]) {
+    let mut contents = vec![];
+    write_name(&mut contents, name.as_bytes());
+    contents.extend_from_slice(payload);
+
+    binary.push(0);
+    write_leb128_u32(binary, contents.len() as u32);
+    binary.extend_from_slice(&contents);
+}
+
+/// Encode `data` as standard, padded base64.
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(triple >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leb128() {
+        let encode = |value| {
+            let mut buf = vec![];
+            write_leb128_u32(&mut buf, value);
+            buf
+        };
+        assert_eq!(encode(0), [0x00]);
+        assert_eq!(encode(127), [0x7f]);
+        assert_eq!(encode(128), [0x80, 0x01]);
+        assert_eq!(encode(624485), [0xe5, 0x8e, 0x26]);
+        assert_eq!(encode(u32::MAX), [0xff, 0xff, 0xff, 0xff, 0x0f]);
+    }
+
+    #[test]
+    fn test_read_leb128() {
+        for value in [0, 127, 128, 624485, u32::MAX] {
+            let mut buf = vec![];
+            write_leb128_u32(&mut buf, value);
+            let len = buf.len();
+            buf.push(0xaa);
+            assert_eq!(read_leb128_u32(&buf), Some((value, len)));
+        }
+        assert_eq!(read_leb128_u32(&[]), None);
+        assert_eq!(read_leb128_u32(&[0x80]), None);
+        assert_eq!(read_leb128_u32(&[0xff, 0xff, 0xff, 0xff, 0x1f]), None);
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_encode(&[0xff, 0xfe]), "//4=");
+    }
+}
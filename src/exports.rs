@@ -0,0 +1,204 @@
+use crate::{name_from_raw, Module};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::ptr;
+
+/// The kind of an import or export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExternalKind {
+    Function,
+    Table,
+    Memory,
+    Global,
+    Tag,
+}
+
+impl ExternalKind {
+    pub(crate) fn from_raw(raw: binaryen_sys::BinaryenExternalKind) -> ExternalKind {
+        unsafe {
+            if raw == binaryen_sys::BinaryenExternalFunction() {
+                ExternalKind::Function
+            } else if raw == binaryen_sys::BinaryenExternalTable() {
+                ExternalKind::Table
+            } else if raw == binaryen_sys::BinaryenExternalMemory() {
+                ExternalKind::Memory
+            } else if raw == binaryen_sys::BinaryenExternalGlobal() {
+                ExternalKind::Global
+            } else if raw == binaryen_sys::BinaryenExternalTag() {
+                ExternalKind::Tag
+            } else {
+                unreachable!("unknown external kind: {}", raw)
+            }
+        }
+    }
+}
+
+/// An export of a module.
+///
+/// This borrows the module it belongs to, so it can't outlive it or observe the module being
+/// changed.
+#[derive(Clone, Copy)]
+pub struct Export<'a> {
+    raw: binaryen_sys::BinaryenExportRef,
+    _module: PhantomData<&'a Module>,
+}
+
+impl<'a> Export<'a> {
+    /// The name the item is exported under.
+    pub fn name(&self) -> &'a str {
+        unsafe { name_from_raw(binaryen_sys::BinaryenExportGetName(self.raw)) }
+    }
+
+    /// The kind of the exported item.
+    pub fn kind(&self) -> ExternalKind {
+        ExternalKind::from_raw(unsafe { binaryen_sys::BinaryenExportGetKind(self.raw) })
+    }
+
+    /// The internal name of the exported item, e.g. the name of the exported function.
+    pub fn value(&self) -> &'a str {
+        unsafe { name_from_raw(binaryen_sys::BinaryenExportGetValue(self.raw)) }
+    }
+}
+
+impl<'a> std::fmt::Debug for Export<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Export")
+            .field("name", &self.name())
+            .field("kind", &self.kind())
+            .field("value", &self.value())
+            .finish()
+    }
+}
+
+impl Module {
+    /// Look up an export by the name it's exported under.
+    pub fn get_export(&self, name: &str) -> Option<Export<'_>> {
+        let name = CString::new(name).ok()?;
+        let raw = unsafe { binaryen_sys::BinaryenGetExport(self.inner.raw, name.as_ptr()) };
+        if raw.is_null() {
+            return None;
+        }
+        Some(Export {
+            raw,
+            _module: PhantomData,
+        })
+    }
+
+    /// The kind, module and base names of all imports, in Binaryen's order of functions,
+    /// tables, memories, globals and tags.
+    pub(crate) fn import_names(&self) -> Vec<(ExternalKind, &str, &str)> {
+        let raw = self.inner.raw;
+        unsafe {
+            let count = binaryen_sys::BinaryenShimGetNumImports(raw) as usize;
+            let mut kinds = vec![0; count];
+            let mut modules = vec![ptr::null(); count];
+            let mut bases = vec![ptr::null(); count];
+            binaryen_sys::BinaryenShimGetImports(
+                raw,
+                kinds.as_mut_ptr(),
+                modules.as_mut_ptr(),
+                bases.as_mut_ptr(),
+            );
+            kinds
+                .into_iter()
+                .zip(modules)
+                .zip(bases)
+                .map(|((kind, module), base)| {
+                    (
+                        ExternalKind::from_raw(kind),
+                        name_from_raw(module),
+                        name_from_raw(base),
+                    )
+                })
+                .collect()
+        }
+    }
+
+    /// Count the imports of the module by the module name they are imported from, e.g.
+    /// `env` or `wasi_snapshot_preview1`.
+    ///
+    /// ```
+    /// # let input = wat::parse_str(r#"(module (import "wasi_snapshot_preview1" "proc_exit" (func (param i32))))"#).unwrap();
+    /// # let module = binaryen::Module::read(&input).unwrap();
+    /// let modules = module.import_modules();
+    /// assert!(modules.keys().all(|name| name == "wasi_snapshot_preview1"));
+    /// ```
+    pub fn import_modules(&self) -> BTreeMap<String, usize> {
+        let mut modules = BTreeMap::new();
+        for (_, module, _) in self.import_names() {
+            *modules.entry(module.to_string()).or_insert(0) += 1;
+        }
+        modules
+    }
+
+    /// Iterate over the exports of the module, in order.
+    pub fn exports(&self) -> impl Iterator<Item = Export<'_>> + '_ {
+        let raw_module = self.inner.raw;
+        let count = unsafe { binaryen_sys::BinaryenGetNumExports(raw_module) };
+        (0..count).map(move |index| Export {
+            raw: unsafe { binaryen_sys::BinaryenGetExportByIndex(raw_module, index) },
+            _module: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_export() {
+        let input = wat::parse_str(
+            r#"
+            (module
+                (memory (export "memory") 1)
+                (func $main (export "_start"))
+            )
+            "#,
+        )
+        .unwrap();
+        let module = Module::read(&input).unwrap();
+
+        let start = module.get_export("_start").unwrap();
+        assert_eq!(start.name(), "_start");
+        assert_eq!(start.kind(), ExternalKind::Function);
+        assert_eq!(start.value(), "main");
+
+        assert_eq!(
+            module.get_export("memory").unwrap().kind(),
+            ExternalKind::Memory
+        );
+        assert!(module.get_export("main").is_none());
+        assert!(module.get_export("nul\0").is_none());
+
+        let names: Vec<_> = module.exports().map(|export| export.name()).collect();
+        assert_eq!(names, ["memory", "_start"]);
+    }
+
+    #[test]
+    fn test_import_modules() {
+        let input = wat::parse_str(
+            r#"
+            (module
+                (import "env" "memory" (memory 1))
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                (import "env" "stack_pointer" (global (mut i32)))
+            )
+            "#,
+        )
+        .unwrap();
+        let module = Module::read(&input).unwrap();
+        let modules: Vec<_> = module.import_modules().into_iter().collect();
+        assert_eq!(
+            modules,
+            [
+                ("env".to_string(), 2),
+                ("wasi_snapshot_preview1".to_string(), 2)
+            ]
+        );
+        assert!(Module::new().import_modules().is_empty());
+    }
+}
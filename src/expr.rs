@@ -0,0 +1,802 @@
+use crate::{take_shim_string, Function, InnerModule, Module, Ty, UnaryOp, ValueTy};
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::ptr;
+use std::rc::Rc;
+
+/// An expression, created by one of the builder methods of [`Module`].
+///
+/// Expressions are allocated in the module they were created by, and can only be used in that
+/// module. Each expression can be used once, e.g. as an operand of another expression or as a
+/// function body, which is why builders take their operands by value.
+///
+/// The builders panic if given an expression of another module, or a name or label containing
+/// a NUL byte. They do so before handing anything to Binaryen, so the module stays intact and
+/// can be used further if the panic is caught.
+pub struct Expr {
+    module: Rc<InnerModule>,
+    raw: binaryen_sys::BinaryenExpressionRef,
+}
+
+impl Expr {
+    pub(crate) fn from_raw(module: &Module, raw: binaryen_sys::BinaryenExpressionRef) -> Expr {
+        Expr::from_inner(&module.inner, raw)
+    }
+
+    pub(crate) fn from_inner(
+        module: &Rc<InnerModule>,
+        raw: binaryen_sys::BinaryenExpressionRef,
+    ) -> Expr {
+        Expr {
+            module: Rc::clone(module),
+            raw,
+        }
+    }
+
+    pub(crate) fn is_from(&self, module: &Rc<InnerModule>) -> bool {
+        Rc::ptr_eq(module, &self.module)
+    }
+
+    pub(crate) fn into_raw(self) -> binaryen_sys::BinaryenExpressionRef {
+        self.raw
+    }
+
+    /// Print the expression in the text format to stdout.
+    pub fn print(&self) {
+        unsafe { binaryen_sys::BinaryenExpressionPrint(self.raw) }
+    }
+
+    /// Get the expression in the text format, as printed by [`Expr::print`].
+    ///
+    /// Locals are referred to by index and the output has no trailing newline, which makes this
+    /// suitable for snapshot tests of generated code.
+    pub fn to_text(&self) -> String {
+        unsafe { expr_to_text(self.raw) }
+    }
+}
+
+/// An expression that is part of a module, e.g. the body of a function.
+///
+/// Unlike [`Expr`], this borrows the module it belongs to and can only be looked at, not used
+/// to build other expressions.
+#[derive(Clone, Copy)]
+pub struct ExprRef<'a> {
+    raw: binaryen_sys::BinaryenExpressionRef,
+    _module: PhantomData<&'a Module>,
+}
+
+impl<'a> ExprRef<'a> {
+    pub(crate) fn from_raw(raw: binaryen_sys::BinaryenExpressionRef) -> ExprRef<'a> {
+        ExprRef {
+            raw,
+            _module: PhantomData,
+        }
+    }
+
+    /// Print the expression in the text format to stdout.
+    pub fn print(&self) {
+        unsafe { binaryen_sys::BinaryenExpressionPrint(self.raw) }
+    }
+
+    /// Get the expression in the text format, see [`Expr::to_text`].
+    pub fn to_text(&self) -> String {
+        unsafe { expr_to_text(self.raw) }
+    }
+}
+
+unsafe fn expr_to_text(raw: binaryen_sys::BinaryenExpressionRef) -> String {
+    let text = take_shim_string(binaryen_sys::BinaryenShimExpressionToText(raw))
+        .expect("printing always produces text");
+    text.trim_end_matches('\n').to_string()
+}
+
+/// A data segment for [`Module::set_memory`].
+pub struct Segment<'a> {
+    pub kind: SegmentKind,
+    pub data: &'a [u8],
+}
+
+/// Whether a [`Segment`] is copied into memory on instantiation.
+pub enum SegmentKind {
+    /// The data is copied to the given address on instantiation, an i32 constant expression.
+    Active(Expr),
+    /// The data is only copied into memory by `memory.init`.
+    Passive,
+}
+
+/// Check that the expression was created by the given module.
+///
+/// Expressions live in the arena of the module that created them, so using one in another
+/// module could leave it dangling once its own module is gone.
+pub(crate) fn is_expr_from_same_module(module: &Module, expr: &Expr) -> bool {
+    expr.is_from(&module.inner)
+}
+
+impl Module {
+    fn take_expr(&self, expr: Expr) -> binaryen_sys::BinaryenExpressionRef {
+        assert!(
+            is_expr_from_same_module(self, &expr),
+            "expression belongs to another module"
+        );
+        expr.into_raw()
+    }
+
+    fn take_optional_expr(&self, expr: Option<Expr>) -> binaryen_sys::BinaryenExpressionRef {
+        expr.map_or(ptr::null_mut(), |expr| self.take_expr(expr))
+    }
+
+    fn take_exprs(&self, exprs: Vec<Expr>) -> Vec<binaryen_sys::BinaryenExpressionRef> {
+        exprs.into_iter().map(|expr| self.take_expr(expr)).collect()
+    }
+
+    /// Add a function to the module.
+    ///
+    /// `var_tys` are the types of the locals declared in addition to the params, which come
+    /// first in the local index space.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the module already has a function with the given name, if the name contains a
+    /// NUL byte or if `body` was created by another module.
+    pub fn add_fn(
+        &mut self,
+        name: &str,
+        params: Ty,
+        results: Ty,
+        var_tys: &[ValueTy],
+        body: Expr,
+    ) -> Function<'_> {
+        let name = CString::new(name).expect("function names contain no NUL bytes");
+        let body = self.take_expr(body);
+        let mut var_tys: Vec<_> = var_tys.iter().map(|ty| ty.to_raw()).collect();
+        unsafe {
+            let existing = binaryen_sys::BinaryenGetFunction(self.inner.raw, name.as_ptr());
+            assert!(existing.is_null(), "duplicate function {:?}", name);
+            let raw = binaryen_sys::BinaryenAddFunction(
+                self.inner.raw,
+                name.as_ptr(),
+                params.to_raw(),
+                results.to_raw(),
+                var_tys.as_mut_ptr(),
+                var_tys.len() as u32,
+                body,
+            );
+            Function::from_raw(raw)
+        }
+    }
+
+    /// Add a global to the module, initialized to `init`.
+    ///
+    /// `init` must be a constant expression, e.g. a `const`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the module already has a global with the given name, if the name contains a
+    /// NUL byte or if `init` was created by another module.
+    pub fn add_global(&mut self, name: &str, ty: ValueTy, mutable: bool, init: Expr) {
+        let name = CString::new(name).expect("global names contain no NUL bytes");
+        let init = self.take_expr(init);
+        unsafe {
+            let existing = binaryen_sys::BinaryenGetGlobal(self.inner.raw, name.as_ptr());
+            assert!(existing.is_null(), "duplicate global {:?}", name);
+            binaryen_sys::BinaryenAddGlobal(
+                self.inner.raw,
+                name.as_ptr(),
+                ty.to_raw(),
+                mutable,
+                init,
+            );
+        }
+    }
+
+    /// Set the memory of the module, replacing any memory and data segments it had.
+    ///
+    /// `initial` and `maximum` are in pages of 64KiB. The memory is exported as `export_name` if
+    /// given.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the export name contains a NUL byte or if an offset was created by another
+    /// module.
+    pub fn set_memory(
+        &mut self,
+        initial: u32,
+        maximum: Option<u32>,
+        export_name: Option<&str>,
+        segments: Vec<Segment>,
+        shared: bool,
+    ) {
+        let export_name =
+            export_name.map(|name| CString::new(name).expect("export names contain no NUL bytes"));
+        let mut datas = vec![];
+        let mut passives = vec![];
+        let mut offsets = vec![];
+        let mut sizes = vec![];
+        for segment in segments {
+            let offset = match segment.kind {
+                SegmentKind::Active(offset) => Some(offset),
+                SegmentKind::Passive => None,
+            };
+            datas.push(segment.data.as_ptr() as *const c_char);
+            passives.push(offset.is_none());
+            offsets.push(self.take_optional_expr(offset));
+            sizes.push(segment.data.len() as u32);
+        }
+        unsafe {
+            binaryen_sys::BinaryenSetMemory(
+                self.inner.raw,
+                initial,
+                // Binaryen treats a maximum of -1 as no maximum.
+                maximum.unwrap_or(u32::MAX),
+                export_name
+                    .as_ref()
+                    .map_or(ptr::null(), |name| name.as_ptr()),
+                ptr::null_mut(),
+                datas.as_mut_ptr(),
+                passives.as_mut_ptr(),
+                offsets.as_mut_ptr(),
+                sizes.as_mut_ptr(),
+                datas.len() as u32,
+                shared,
+                false,
+                ptr::null(),
+            );
+        }
+    }
+
+    /// Create a `nop` expression.
+    pub fn nop(&self) -> Expr {
+        Expr::from_raw(self, unsafe { binaryen_sys::BinaryenNop(self.inner.raw) })
+    }
+
+    /// Create an `unreachable` expression, which traps.
+    pub fn unreachable(&self) -> Expr {
+        Expr::from_raw(self, unsafe {
+            binaryen_sys::BinaryenUnreachable(self.inner.raw)
+        })
+    }
+
+    /// Create a `drop` expression, discarding the result of `value`.
+    pub fn drop(&self, value: Expr) -> Expr {
+        let value = self.take_expr(value);
+        Expr::from_raw(self, unsafe {
+            binaryen_sys::BinaryenDrop(self.inner.raw, value)
+        })
+    }
+
+    /// Create a `select` expression, evaluating to `if_true` if the i32 `condition` is non-zero
+    /// and to `if_false` otherwise.
+    ///
+    /// Both values are always evaluated. The type is inferred from the values if `ty` is
+    /// `None`; giving it explicitly emits the typed `select` instruction.
+    pub fn select(
+        &self,
+        condition: Expr,
+        if_true: Expr,
+        if_false: Expr,
+        ty: Option<ValueTy>,
+    ) -> Expr {
+        let condition = self.take_expr(condition);
+        let if_true = self.take_expr(if_true);
+        let if_false = self.take_expr(if_false);
+        let ty = match ty {
+            Some(ty) => ty.to_raw(),
+            None => unsafe { binaryen_sys::BinaryenTypeAuto() },
+        };
+        let raw = unsafe {
+            binaryen_sys::BinaryenSelect(self.inner.raw, condition, if_true, if_false, ty)
+        };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create an `i32.const` expression.
+    pub fn i32_const(&self, value: i32) -> Expr {
+        let raw = unsafe {
+            binaryen_sys::BinaryenConst(self.inner.raw, binaryen_sys::BinaryenLiteralInt32(value))
+        };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create a `local.get` expression reading the local with the given index.
+    pub fn local_get(&self, index: u32, ty: ValueTy) -> Expr {
+        let raw = unsafe { binaryen_sys::BinaryenLocalGet(self.inner.raw, index, ty.to_raw()) };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create a `local.set` expression writing `value` to the local with the given index.
+    pub fn local_set(&self, index: u32, value: Expr) -> Expr {
+        let value = self.take_expr(value);
+        let raw = unsafe { binaryen_sys::BinaryenLocalSet(self.inner.raw, index, value) };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create a `block` expression running `children` in order.
+    ///
+    /// Branches to `label` leave the block. The type of the block is inferred from the last
+    /// child and the branches to it if `ty` is `None`.
+    pub fn block(&self, label: Option<&str>, children: Vec<Expr>, ty: Option<Ty>) -> Expr {
+        let label = label.map(|label| CString::new(label).expect("labels contain no NUL bytes"));
+        let mut children = self.take_exprs(children);
+        let ty = match ty {
+            Some(ty) => ty.to_raw(),
+            None => unsafe { binaryen_sys::BinaryenTypeAuto() },
+        };
+        let raw = unsafe {
+            binaryen_sys::BinaryenBlock(
+                self.inner.raw,
+                label.as_ref().map_or(ptr::null(), |label| label.as_ptr()),
+                children.as_mut_ptr(),
+                children.len() as u32,
+                ty,
+            )
+        };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create an `if` expression, with an `else` arm if `if_false` is given.
+    pub fn if_(&self, condition: Expr, if_true: Expr, if_false: Option<Expr>) -> Expr {
+        let condition = self.take_expr(condition);
+        let if_true = self.take_expr(if_true);
+        let if_false = self.take_optional_expr(if_false);
+        let raw = unsafe { binaryen_sys::BinaryenIf(self.inner.raw, condition, if_true, if_false) };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create a `loop` expression running `body`.
+    ///
+    /// Branches to `label` jump back to the start of the loop.
+    pub fn loop_(&self, label: Option<&str>, body: Expr) -> Expr {
+        let label = label.map(|label| CString::new(label).expect("labels contain no NUL bytes"));
+        let body = self.take_expr(body);
+        let raw = unsafe {
+            binaryen_sys::BinaryenLoop(
+                self.inner.raw,
+                label.as_ref().map_or(ptr::null(), |label| label.as_ptr()),
+                body,
+            )
+        };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create a branch to the block or loop named `label`, passing `value` if given.
+    ///
+    /// The branch is a `br_if` taken if the i32 `condition` is non-zero, if given.
+    pub fn br(&self, label: &str, condition: Option<Expr>, value: Option<Expr>) -> Expr {
+        let label = CString::new(label).expect("labels contain no NUL bytes");
+        let condition = self.take_optional_expr(condition);
+        let value = self.take_optional_expr(value);
+        let raw = unsafe {
+            binaryen_sys::BinaryenBreak(self.inner.raw, label.as_ptr(), condition, value)
+        };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create a `call` expression calling the function named `target`.
+    ///
+    /// `ret` must be the result type of the function.
+    pub fn call(&self, target: &str, operands: Vec<Expr>, ret: Ty) -> Expr {
+        let target = CString::new(target).expect("function names contain no NUL bytes");
+        let mut operands = self.take_exprs(operands);
+        let raw = unsafe {
+            binaryen_sys::BinaryenCall(
+                self.inner.raw,
+                target.as_ptr(),
+                operands.as_mut_ptr(),
+                operands.len() as u32,
+                ret.to_raw(),
+            )
+        };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create a `call_indirect` expression calling the function at `target_index` in `table`.
+    ///
+    /// `params` and `results` form the signature the function is expected to have, which is
+    /// checked at runtime.
+    pub fn call_indirect(
+        &self,
+        table: &str,
+        target_index: Expr,
+        operands: Vec<Expr>,
+        params: Ty,
+        results: Ty,
+    ) -> Expr {
+        let table = CString::new(table).expect("table names contain no NUL bytes");
+        let target_index = self.take_expr(target_index);
+        let mut operands = self.take_exprs(operands);
+        let raw = unsafe {
+            binaryen_sys::BinaryenCallIndirect(
+                self.inner.raw,
+                table.as_ptr(),
+                target_index,
+                operands.as_mut_ptr(),
+                operands.len() as u32,
+                params.to_raw(),
+                results.to_raw(),
+            )
+        };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create a load of `bytes` bytes of type `ty` from the address `ptr` plus `offset`.
+    ///
+    /// Loading fewer bytes than `ty` has, e.g. `i32.load8_s`, extends the value, with its sign
+    /// if `signed`, which is ignored otherwise. `align` is the alignment in bytes the address is
+    /// expected to have, up to `bytes`, or 0 for the natural alignment of `bytes`.
+    ///
+    /// The load accesses the memory of the module, so it must have one, see
+    /// [`Module::set_memory`].
+    pub fn load(
+        &self,
+        bytes: u32,
+        signed: bool,
+        offset: u32,
+        align: u32,
+        ty: ValueTy,
+        ptr: Expr,
+    ) -> Expr {
+        debug_assert!(align == 0 || (align.is_power_of_two() && align <= bytes));
+        let ptr = self.take_expr(ptr);
+        let raw = unsafe {
+            binaryen_sys::BinaryenLoad(
+                self.inner.raw,
+                bytes,
+                signed,
+                offset,
+                align,
+                ty.to_raw(),
+                ptr,
+                ptr::null(),
+            )
+        };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create a store of the `bytes` low bytes of `value` of type `ty` to the address `ptr` plus
+    /// `offset`.
+    ///
+    /// `align` works like for [`Module::load`].
+    pub fn store(
+        &self,
+        bytes: u32,
+        offset: u32,
+        align: u32,
+        ptr: Expr,
+        value: Expr,
+        ty: ValueTy,
+    ) -> Expr {
+        debug_assert!(align == 0 || (align.is_power_of_two() && align <= bytes));
+        let ptr = self.take_expr(ptr);
+        let value = self.take_expr(value);
+        let raw = unsafe {
+            binaryen_sys::BinaryenStore(
+                self.inner.raw,
+                bytes,
+                offset,
+                align,
+                ptr,
+                value,
+                ty.to_raw(),
+                ptr::null(),
+            )
+        };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create a unary operation, e.g. a conversion, applied to `value`.
+    pub fn unary(&self, op: UnaryOp, value: Expr) -> Expr {
+        let value = self.take_expr(value);
+        let raw = unsafe { binaryen_sys::BinaryenUnary(self.inner.raw, op.to_raw(), value) };
+        Expr::from_raw(self, raw)
+    }
+
+    /// Create a `return` expression, returning `value` if given.
+    pub fn return_(&self, value: Option<Expr>) -> Expr {
+        let value = self.take_optional_expr(value);
+        Expr::from_raw(self, unsafe {
+            binaryen_sys::BinaryenReturn(self.inner.raw, value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_fn() {
+        let mut module = Module::new();
+        let body = module.return_(Some(module.local_get(0, ValueTy::I32)));
+        module.add_fn(
+            "id",
+            ValueTy::I32.into(),
+            ValueTy::I32.into(),
+            &[ValueTy::I64],
+            body,
+        );
+        module.assert_valid();
+        assert!(module
+            .write_text()
+            .contains("(func $id (param $0 i32) (result i32)"));
+    }
+
+    #[test]
+    fn test_add_global() {
+        let mut module = Module::new();
+        let init = module.i32_const(1024);
+        module.add_global("stack_pointer", ValueTy::I32, true, init);
+        module.assert_valid();
+        assert!(module
+            .write_text()
+            .contains("(global $stack_pointer (mut i32) (i32.const 1024))"));
+        assert_eq!(module.metrics().globals, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate global")]
+    fn test_duplicate_global() {
+        let mut module = Module::new();
+        let init = module.i32_const(0);
+        module.add_global("g", ValueTy::I32, false, init);
+        let init = module.i32_const(1);
+        module.add_global("g", ValueTy::I32, false, init);
+    }
+
+    #[test]
+    fn test_set_memory() {
+        let mut module = Module::new();
+        let segments = vec![
+            Segment {
+                kind: SegmentKind::Active(module.i32_const(16)),
+                data: b"hello",
+            },
+            Segment {
+                kind: SegmentKind::Passive,
+                data: b"world",
+            },
+        ];
+        module.set_memory(1, Some(2), Some("memory"), segments, false);
+        module.assert_valid();
+
+        let module = Module::read(&module.write()).unwrap();
+        assert!(module.has_memory());
+        assert_eq!(
+            module.get_export("memory").unwrap().kind(),
+            crate::ExternalKind::Memory
+        );
+        let text = module.write_text();
+        assert!(text.contains("(memory $0 1 2)"), "{}", text);
+        assert!(text.contains(r#"(i32.const 16) "hello")"#), "{}", text);
+        assert!(text.contains(r#""world")"#), "{}", text);
+        assert_eq!(module.metrics().data_segments, 2);
+    }
+
+    #[test]
+    fn test_calls() {
+        let input = wat::parse_str(
+            r#"
+            (module
+                (table 1 funcref)
+                (elem (i32.const 0) $double)
+                (func $double (param i32) (result i32)
+                    (i32.add (local.get 0) (local.get 0))
+                )
+            )
+            "#,
+        )
+        .unwrap();
+        let mut module = Module::read(&input).unwrap();
+
+        let i32_ty = Ty::value(ValueTy::I32);
+        let direct = module.call("double", vec![module.local_get(0, ValueTy::I32)], i32_ty);
+        module.add_fn("direct", i32_ty, i32_ty, &[], direct);
+
+        let indirect = module.call_indirect(
+            "0",
+            module.i32_const(0),
+            vec![module.local_get(0, ValueTy::I32)],
+            i32_ty,
+            i32_ty,
+        );
+        module.add_fn("indirect", i32_ty, i32_ty, &[], indirect);
+        module.assert_valid();
+
+        let module = Module::read(&module.write()).unwrap();
+        let text = module.write_text();
+        assert!(text.contains("(call $double"), "{}", text);
+        assert!(text.contains("(call_indirect"), "{}", text);
+    }
+
+    #[test]
+    fn test_load_store() {
+        let mut module = Module::new();
+        module.set_memory(1, None, None, vec![], false);
+
+        let store = module.store(
+            4,
+            8,
+            0,
+            module.local_get(0, ValueTy::I32),
+            module.i32_const(-2),
+            ValueTy::I32,
+        );
+        let load = module.load(
+            4,
+            false,
+            8,
+            0,
+            ValueTy::I32,
+            module.local_get(0, ValueTy::I32),
+        );
+        assert_eq!(
+            store.to_text(),
+            "(i32.store offset=8\n (local.get $0)\n (i32.const -2)\n)"
+        );
+        assert_eq!(load.to_text(), "(i32.load offset=8\n (local.get $0)\n)");
+
+        let byte = module.load(1, true, 0, 1, ValueTy::I32, module.i32_const(0));
+        assert!(byte.to_text().starts_with("(i32.load8_s"));
+        let unaligned = module.load(4, false, 0, 1, ValueTy::I32, module.i32_const(0));
+        assert!(unaligned.to_text().starts_with("(i32.load align=1"));
+
+        module.add_fn("store", ValueTy::I32.into(), Ty::none(), &[], store);
+        module.add_fn("load", ValueTy::I32.into(), ValueTy::I32.into(), &[], load);
+        module.assert_valid();
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let mut module = Module::new();
+
+        // Loop until local 0 is cleared, then return 1 if local 1 is set and 0 otherwise.
+        let count_down = module.loop_(
+            Some("loop"),
+            module.block(
+                None,
+                vec![
+                    module.local_set(0, module.i32_const(0)),
+                    module.br("loop", Some(module.local_get(0, ValueTy::I32)), None),
+                ],
+                None,
+            ),
+        );
+        let result = module.if_(
+            module.local_get(1, ValueTy::I32),
+            module.i32_const(1),
+            Some(module.i32_const(0)),
+        );
+        let body = module.block(
+            Some("body"),
+            vec![count_down, result],
+            Some(ValueTy::I32.into()),
+        );
+        let params = Ty::tuple(&[ValueTy::I32, ValueTy::I32]);
+        module.add_fn("count", params, ValueTy::I32.into(), &[], body);
+        module.assert_valid();
+
+        let text = module.write_text();
+        assert!(text.contains("(loop $loop"), "{}", text);
+        assert!(text.contains("(br_if $loop"), "{}", text);
+        assert!(text.contains("(if (result i32)"), "{}", text);
+    }
+
+    #[test]
+    fn test_unreachable_and_drop() {
+        let mut module = Module::new();
+        let body = module.block(
+            None,
+            vec![module.drop(module.i32_const(42)), module.unreachable()],
+            None,
+        );
+        module.add_fn("trap", Ty::none(), ValueTy::I32.into(), &[], body);
+        module.assert_valid();
+
+        let module = Module::read(&module.write()).unwrap();
+        let text = module.write_text();
+        assert!(text.contains("(drop"), "{}", text);
+        assert!(text.contains("(unreachable)"), "{}", text);
+    }
+
+    #[test]
+    fn test_select() {
+        let mut module = Module::new();
+        let body = module.select(
+            module.local_get(0, ValueTy::I32),
+            module.i32_const(10),
+            module.i32_const(20),
+            None,
+        );
+        module.add_fn("pick", ValueTy::I32.into(), ValueTy::I32.into(), &[], body);
+
+        let typed = module.select(
+            module.local_get(0, ValueTy::I32),
+            module.i32_const(1),
+            module.i32_const(2),
+            Some(ValueTy::I32),
+        );
+        module.add_fn(
+            "pick_typed",
+            ValueTy::I32.into(),
+            ValueTy::I32.into(),
+            &[],
+            typed,
+        );
+        module.assert_valid();
+        assert!(module.write_text().contains("(select"));
+    }
+
+    #[test]
+    fn test_unary() {
+        let mut module = Module::new();
+
+        // (i32) -> f64, via a sign-extended i64
+        let extended = module.unary(UnaryOp::ExtendSI32, module.local_get(0, ValueTy::I32));
+        let body = module.unary(UnaryOp::ConvertSI64ToF64, extended);
+        assert_eq!(
+            body.to_text(),
+            "(f64.convert_i64_s\n (i64.extend_i32_s\n  (local.get $0)\n )\n)"
+        );
+        module.add_fn("widen", ValueTy::I32.into(), ValueTy::F64.into(), &[], body);
+
+        let sqrt = module.unary(UnaryOp::SqrtF64, module.local_get(0, ValueTy::F64));
+        let body = module.unary(UnaryOp::TruncSF64ToI32, sqrt);
+        module.add_fn("isqrt", ValueTy::F64.into(), ValueTy::I32.into(), &[], body);
+
+        let body = module.unary(UnaryOp::ClzI32, module.local_get(0, ValueTy::I32));
+        module.add_fn("clz", ValueTy::I32.into(), ValueTy::I32.into(), &[], body);
+        module.assert_valid();
+    }
+
+    #[test]
+    fn test_to_text() {
+        let module = Module::new();
+        assert_eq!(module.nop().to_text(), "(nop)");
+
+        let expr = module.local_set(1, module.i32_const(42));
+        assert_eq!(expr.to_text(), "(local.set $1\n (i32.const 42)\n)");
+    }
+
+    #[test]
+    #[should_panic(expected = "expression belongs to another module")]
+    fn test_expr_from_other_module() {
+        let mut module = Module::new();
+        let other = Module::new();
+        module.add_fn("f", Ty::none(), Ty::none(), &[], other.nop());
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate function")]
+    fn test_duplicate_fn() {
+        let mut module = Module::new();
+        let body = module.nop();
+        module.add_fn("f", Ty::none(), Ty::none(), &[], body);
+        let body = module.nop();
+        module.add_fn("f", Ty::none(), Ty::none(), &[], body);
+    }
+
+    #[test]
+    fn test_panics_leave_module_intact() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut module = Module::new();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let body = module.nop();
+            module.add_fn("bad\0name", Ty::none(), Ty::none(), &[], body);
+        }));
+        assert!(result.is_err());
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let value = module.i32_const(0);
+            module.br("bad\0label", None, Some(value))
+        }));
+        assert!(result.is_err());
+
+        // Nothing was added, and the expressions created in the closures were released.
+        assert!(module.get_function("bad").is_none());
+        assert_eq!(Rc::strong_count(&module.inner), 1);
+
+        let body = module.nop();
+        module.add_fn("good", Ty::none(), Ty::none(), &[], body);
+        module.assert_valid();
+    }
+}
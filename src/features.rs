@@ -0,0 +1,345 @@
+use std::str::FromStr;
+use std::{error, fmt};
+
+/// A WebAssembly feature (proposal) supported by Binaryen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// Threads and atomic operations.
+    Atomics,
+    BulkMemory,
+    MutableGlobals,
+    NontrappingFpToInt,
+    SignExt,
+    Simd,
+    ExceptionHandling,
+    TailCall,
+    ReferenceTypes,
+    Multivalue,
+    Gc,
+    Memory64,
+    RelaxedSimd,
+    ExtendedConst,
+    Strings,
+    MultiMemory,
+}
+
+impl Feature {
+    /// All the features known to this crate.
+    pub const ALL: &'static [Feature] = &[
+        Feature::Atomics,
+        Feature::BulkMemory,
+        Feature::MutableGlobals,
+        Feature::NontrappingFpToInt,
+        Feature::SignExt,
+        Feature::Simd,
+        Feature::ExceptionHandling,
+        Feature::TailCall,
+        Feature::ReferenceTypes,
+        Feature::Multivalue,
+        Feature::Gc,
+        Feature::Memory64,
+        Feature::RelaxedSimd,
+        Feature::ExtendedConst,
+        Feature::Strings,
+        Feature::MultiMemory,
+    ];
+
+    /// The canonical name of the feature, as used by `wasm-opt`'s `--enable-*` flags.
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Atomics => "threads",
+            Feature::BulkMemory => "bulk-memory",
+            Feature::MutableGlobals => "mutable-globals",
+            Feature::NontrappingFpToInt => "nontrapping-float-to-int",
+            Feature::SignExt => "sign-ext",
+            Feature::Simd => "simd",
+            Feature::ExceptionHandling => "exception-handling",
+            Feature::TailCall => "tail-call",
+            Feature::ReferenceTypes => "reference-types",
+            Feature::Multivalue => "multivalue",
+            Feature::Gc => "gc",
+            Feature::Memory64 => "memory64",
+            Feature::RelaxedSimd => "relaxed-simd",
+            Feature::ExtendedConst => "extended-const",
+            Feature::Strings => "strings",
+            Feature::MultiMemory => "multimemory",
+        }
+    }
+
+    /// The name of the feature in the `target_features` custom section, as used by LLVM.
+    pub fn target_feature_name(self) -> &'static str {
+        match self {
+            Feature::Atomics => "atomics",
+            Feature::NontrappingFpToInt => "nontrapping-fptoint",
+            Feature::Simd => "simd128",
+            other => other.name(),
+        }
+    }
+
+    pub(crate) fn to_raw(self) -> binaryen_sys::BinaryenFeatures {
+        unsafe {
+            match self {
+                Feature::Atomics => binaryen_sys::BinaryenFeatureAtomics(),
+                Feature::BulkMemory => binaryen_sys::BinaryenFeatureBulkMemory(),
+                Feature::MutableGlobals => binaryen_sys::BinaryenFeatureMutableGlobals(),
+                Feature::NontrappingFpToInt => binaryen_sys::BinaryenFeatureNontrappingFPToInt(),
+                Feature::SignExt => binaryen_sys::BinaryenFeatureSignExt(),
+                Feature::Simd => binaryen_sys::BinaryenFeatureSIMD128(),
+                Feature::ExceptionHandling => binaryen_sys::BinaryenFeatureExceptionHandling(),
+                Feature::TailCall => binaryen_sys::BinaryenFeatureTailCall(),
+                Feature::ReferenceTypes => binaryen_sys::BinaryenFeatureReferenceTypes(),
+                Feature::Multivalue => binaryen_sys::BinaryenFeatureMultivalue(),
+                Feature::Gc => binaryen_sys::BinaryenFeatureGC(),
+                Feature::Memory64 => binaryen_sys::BinaryenFeatureMemory64(),
+                Feature::RelaxedSimd => binaryen_sys::BinaryenFeatureRelaxedSIMD(),
+                Feature::ExtendedConst => binaryen_sys::BinaryenFeatureExtendedConst(),
+                Feature::Strings => binaryen_sys::BinaryenFeatureStrings(),
+                Feature::MultiMemory => binaryen_sys::BinaryenFeatureMultiMemory(),
+            }
+        }
+    }
+}
+
+/// Error returned when parsing an unknown feature name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseFeatureError {
+    name: String,
+}
+
+impl ParseFeatureError {
+    /// The name that wasn't recognized.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for ParseFeatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown feature: {}", self.name)
+    }
+}
+
+impl error::Error for ParseFeatureError {}
+
+impl FromStr for Feature {
+    type Err = ParseFeatureError;
+
+    /// Parse a canonical feature name, see [`Feature::name`].
+    fn from_str(s: &str) -> Result<Feature, ParseFeatureError> {
+        Feature::ALL
+            .iter()
+            .copied()
+            .find(|feature| feature.name() == s)
+            .ok_or_else(|| ParseFeatureError {
+                name: s.to_string(),
+            })
+    }
+}
+
+bitflags::bitflags! {
+    /// A set of WebAssembly features.
+    ///
+    /// With the `serde` feature enabled, the set is (de)serialized as a list of canonical feature
+    /// names, e.g. `["simd", "bulk-memory"]`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct Features: u32 {
+        const ATOMICS = 1 << 0;
+        const BULK_MEMORY = 1 << 1;
+        const MUTABLE_GLOBALS = 1 << 2;
+        const NONTRAPPING_FP_TO_INT = 1 << 3;
+        const SIGN_EXT = 1 << 4;
+        const SIMD = 1 << 5;
+        const EXCEPTION_HANDLING = 1 << 6;
+        const TAIL_CALL = 1 << 7;
+        const REFERENCE_TYPES = 1 << 8;
+        const MULTIVALUE = 1 << 9;
+        const GC = 1 << 10;
+        const MEMORY64 = 1 << 11;
+        const RELAXED_SIMD = 1 << 12;
+        const EXTENDED_CONST = 1 << 13;
+        const STRINGS = 1 << 14;
+        const MULTI_MEMORY = 1 << 15;
+    }
+}
+
+impl Features {
+    /// The MVP feature set, i.e. no features at all.
+    pub const MVP: Features = Features::empty();
+
+    /// Iterate over the features in this set.
+    pub fn iter_features(&self) -> impl Iterator<Item = Feature> + '_ {
+        Feature::ALL
+            .iter()
+            .copied()
+            .filter(move |feature| self.contains(Features::from(*feature)))
+    }
+
+    pub(crate) fn to_raw(self) -> binaryen_sys::BinaryenFeatures {
+        self.iter_features()
+            .fold(0, |raw, feature| raw | feature.to_raw())
+    }
+
+    pub(crate) fn from_raw(raw: binaryen_sys::BinaryenFeatures) -> Features {
+        Feature::ALL
+            .iter()
+            .copied()
+            .filter(|feature| raw & feature.to_raw() != 0)
+            .collect()
+    }
+}
+
+impl Default for Features {
+    fn default() -> Features {
+        Features::MVP
+    }
+}
+
+impl From<Feature> for Features {
+    fn from(feature: Feature) -> Features {
+        match feature {
+            Feature::Atomics => Features::ATOMICS,
+            Feature::BulkMemory => Features::BULK_MEMORY,
+            Feature::MutableGlobals => Features::MUTABLE_GLOBALS,
+            Feature::NontrappingFpToInt => Features::NONTRAPPING_FP_TO_INT,
+            Feature::SignExt => Features::SIGN_EXT,
+            Feature::Simd => Features::SIMD,
+            Feature::ExceptionHandling => Features::EXCEPTION_HANDLING,
+            Feature::TailCall => Features::TAIL_CALL,
+            Feature::ReferenceTypes => Features::REFERENCE_TYPES,
+            Feature::Multivalue => Features::MULTIVALUE,
+            Feature::Gc => Features::GC,
+            Feature::Memory64 => Features::MEMORY64,
+            Feature::RelaxedSimd => Features::RELAXED_SIMD,
+            Feature::ExtendedConst => Features::EXTENDED_CONST,
+            Feature::Strings => Features::STRINGS,
+            Feature::MultiMemory => Features::MULTI_MEMORY,
+        }
+    }
+}
+
+impl FromIterator<Feature> for Features {
+    fn from_iter<I: IntoIterator<Item = Feature>>(iter: I) -> Features {
+        iter.into_iter().map(Features::from).collect()
+    }
+}
+
+impl fmt::Display for Feature {
+    /// Write the canonical name of the feature, see [`Feature::name`].
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for Features {
+    /// Write the canonical names of the features separated by commas, e.g. `bulk-memory,simd`.
+    /// The MVP feature set is written as an empty string.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, feature) in self.iter_features().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(feature.name())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Features {
+    type Err = ParseFeatureError;
+
+    /// Parse a list of canonical feature names separated by commas or whitespace, e.g.
+    /// `simd,bulk-memory` or `simd bulk-memory`. This is the inverse of the `Display` impl.
+    fn from_str(s: &str) -> Result<Features, ParseFeatureError> {
+        s.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|name| !name.is_empty())
+            .map(Feature::from_str)
+            .collect()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Features {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter_features().map(Feature::name))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Features {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Features, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        names
+            .iter()
+            .map(|name| name.parse::<Feature>())
+            .collect::<Result<Features, _>>()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_round_trip() {
+        for feature in Feature::ALL {
+            let features = Features::from(*feature);
+            assert_eq!(Features::from_raw(features.to_raw()), features);
+        }
+        let all = Features::all();
+        assert_eq!(all.to_raw(), unsafe { binaryen_sys::BinaryenFeatureAll() });
+        assert_eq!(Features::from_raw(all.to_raw()), all);
+        assert_eq!(Features::MVP.to_raw(), unsafe {
+            binaryen_sys::BinaryenFeatureMVP()
+        });
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            "simd, bulk-memory".parse::<Features>(),
+            Ok(Features::SIMD | Features::BULK_MEMORY)
+        );
+        assert_eq!("".parse::<Features>(), Ok(Features::MVP));
+        assert_eq!("threads".parse::<Feature>(), Ok(Feature::Atomics));
+
+        let err = "simd,bogus".parse::<Features>().unwrap_err();
+        assert_eq!(err.name(), "bogus");
+
+        assert_eq!(
+            "simd threads\tbulk-memory".parse::<Features>(),
+            Ok(Features::SIMD | Features::ATOMICS | Features::BULK_MEMORY)
+        );
+        let err = "simd threads bogus".parse::<Features>().unwrap_err();
+        assert_eq!(err.name(), "bogus");
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Feature::Atomics.to_string(), "threads");
+        assert_eq!(Features::MVP.to_string(), "");
+        assert_eq!(
+            (Features::SIMD | Features::BULK_MEMORY).to_string(),
+            "bulk-memory,simd"
+        );
+        let all = Features::all();
+        assert_eq!(all.to_string().parse::<Features>(), Ok(all));
+    }
+
+    #[test]
+    fn test_names_are_unique() {
+        for feature in Feature::ALL {
+            assert_eq!(feature.name().parse::<Feature>(), Ok(*feature));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let features = Features::SIMD | Features::BULK_MEMORY;
+        let json = serde_json::to_string(&features).unwrap();
+        assert_eq!(json, r#"["bulk-memory","simd"]"#);
+        assert_eq!(serde_json::from_str::<Features>(&json).unwrap(), features);
+        assert!(serde_json::from_str::<Features>(r#"["bogus"]"#).is_err());
+    }
+}
@@ -0,0 +1,456 @@
+use crate::{
+    is_valid_pass, name_from_raw, take_shim_string, CodegenConfig, Expr, ExprRef, InvalidPassError,
+    Module, Ty, ValueTy,
+};
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::ptr;
+use std::{error, fmt};
+
+/// A function of a module.
+///
+/// This borrows the module it belongs to, so it can't outlive it or observe the module being
+/// changed. Use [`Module::get_function_mut`] to change the function.
+#[derive(Clone, Copy)]
+pub struct Function<'a> {
+    raw: binaryen_sys::BinaryenFunctionRef,
+    _module: PhantomData<&'a Module>,
+}
+
+impl<'a> Function<'a> {
+    pub(crate) fn from_raw(raw: binaryen_sys::BinaryenFunctionRef) -> Function<'a> {
+        Function {
+            raw,
+            _module: PhantomData,
+        }
+    }
+
+    /// The name of the function.
+    pub fn name(&self) -> &'a str {
+        unsafe { name_from_raw(binaryen_sys::BinaryenFunctionGetName(self.raw)) }
+    }
+
+    /// The types of the parameters of the function.
+    pub fn params(&self) -> Ty {
+        Ty::from_raw(unsafe { binaryen_sys::BinaryenFunctionGetParams(self.raw) })
+    }
+
+    /// The types of the results of the function.
+    pub fn results(&self) -> Ty {
+        Ty::from_raw(unsafe { binaryen_sys::BinaryenFunctionGetResults(self.raw) })
+    }
+
+    /// The number of locals of the function, including the parameters.
+    pub fn num_locals(&self) -> u32 {
+        unsafe { binaryen_sys::BinaryenFunctionGetNumLocals(self.raw) }
+    }
+
+    /// The type of the local with the given index.
+    ///
+    /// The parameters come first in the local index space, followed by the variables. Returns
+    /// `None` if there is no such local, or if its type is not a [`ValueTy`], e.g. a reference
+    /// type.
+    pub fn local_type(&self, index: u32) -> Option<ValueTy> {
+        if index >= self.num_locals() {
+            return None;
+        }
+        let params = self.params().raw_components();
+        let raw = match params.get(index as usize) {
+            Some(raw) => *raw,
+            None => unsafe {
+                binaryen_sys::BinaryenFunctionGetVar(self.raw, index - params.len() as u32)
+            },
+        };
+        ValueTy::from_raw(raw)
+    }
+
+    /// Get the body of the function.
+    ///
+    /// To use the body in other expressions, take it out with [`FunctionMut::take_body`].
+    pub fn body(&self) -> ExprRef<'a> {
+        ExprRef::from_raw(unsafe { binaryen_sys::BinaryenFunctionGetBody(self.raw) })
+    }
+}
+
+impl<'a> std::fmt::Debug for Function<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name())
+            .field("params", &self.params())
+            .field("results", &self.results())
+            .finish()
+    }
+}
+
+/// Error returned by [`FunctionMut::run_passes`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FunctionPassError {
+    /// The pass is not known to Binaryen.
+    InvalidPass(InvalidPassError),
+    /// The pass works on whole modules, e.g. `inlining`, so it can't run on a single function.
+    NotFunctionParallel(String),
+}
+
+impl FunctionPassError {
+    /// The offending pass name.
+    pub fn pass(&self) -> &str {
+        match self {
+            FunctionPassError::InvalidPass(err) => err.pass(),
+            FunctionPassError::NotFunctionParallel(pass) => pass,
+        }
+    }
+}
+
+impl fmt::Display for FunctionPassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FunctionPassError::InvalidPass(err) => err.fmt(f),
+            FunctionPassError::NotFunctionParallel(pass) => {
+                write!(f, "pass can't run on a single function: {}", pass)
+            }
+        }
+    }
+}
+
+impl error::Error for FunctionPassError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FunctionPassError::InvalidPass(err) => Some(err),
+            FunctionPassError::NotFunctionParallel(_) => None,
+        }
+    }
+}
+
+/// A function of a module that can be changed, see [`Module::get_function_mut`].
+///
+/// This borrows the module mutably, so no other handles to it can be alive meanwhile. Build
+/// the expressions needed for a change before getting the function.
+pub struct FunctionMut<'a> {
+    module: &'a mut Module,
+    raw: binaryen_sys::BinaryenFunctionRef,
+}
+
+impl<'a> FunctionMut<'a> {
+    /// View the function, e.g. to get its name or body.
+    pub fn as_function(&self) -> Function<'_> {
+        Function::from_raw(self.raw)
+    }
+
+    /// Take the body out of the function, to use it in other expressions.
+    ///
+    /// The function is left with an `unreachable` body until it gets a new one with
+    /// [`FunctionMut::set_body`].
+    pub fn take_body(&mut self) -> Expr {
+        unsafe {
+            let body = binaryen_sys::BinaryenFunctionGetBody(self.raw);
+            let unreachable = binaryen_sys::BinaryenUnreachable(self.module.inner.raw);
+            binaryen_sys::BinaryenFunctionSetBody(self.raw, unreachable);
+            Expr::from_raw(self.module, body)
+        }
+    }
+
+    /// Replace the body of the function.
+    ///
+    /// The old body is dropped from the function; take it out with [`FunctionMut::take_body`]
+    /// first to keep using it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `body` was created by another module.
+    pub fn set_body(&mut self, body: Expr) {
+        assert!(
+            body.is_from(&self.module.inner),
+            "expression belongs to another module"
+        );
+        unsafe { binaryen_sys::BinaryenFunctionSetBody(self.raw, body.into_raw()) }
+    }
+
+    /// Run the function-level optimization passes of [`Module::optimize`] on this function
+    /// only.
+    ///
+    /// This is much cheaper than optimizing the whole module after changing a single
+    /// function, but misses everything that needs to look at more than one function: inlining,
+    /// removing unused functions and globals, merging duplicate functions, removing unused
+    /// parameters and results, optimizing globals, and reordering functions. [`Module::optimize`]
+    /// is still needed for the final output.
+    ///
+    /// As this changes the function, it's only available through the handle of
+    /// [`Module::get_function_mut`]. Like for modules, fatal errors of Binaryen turn into panics.
+    pub fn optimize(&mut self, codegen_config: &CodegenConfig) {
+        unsafe { self.run_passes_with_settings(ptr::null_mut(), 0, codegen_config) }
+    }
+
+    /// Run the given passes on this function only, e.g. `simplify-locals` and `vacuum` on a hot
+    /// function.
+    ///
+    /// Returns `Err` without running any passes if one of them is not known to Binaryen, or
+    /// works on whole modules and so can't run on a single function, like `inlining` or
+    /// `remove-unused-module-elements`.
+    pub fn run_passes(
+        &mut self,
+        passes: &[&str],
+        codegen_config: &CodegenConfig,
+    ) -> Result<(), FunctionPassError> {
+        let mut cstr_vec = vec![];
+        for pass in passes {
+            if !is_valid_pass(pass) {
+                return Err(FunctionPassError::InvalidPass(InvalidPassError::new(pass)));
+            }
+            let cstr = CString::new(*pass).unwrap();
+            if unsafe { binaryen_sys::BinaryenShimIsPassFunctionParallel(cstr.as_ptr()) } == 0 {
+                return Err(FunctionPassError::NotFunctionParallel(pass.to_string()));
+            }
+            cstr_vec.push(cstr);
+        }
+
+        let mut ptr_vec: Vec<_> = cstr_vec.iter().map(|pass| pass.as_ptr()).collect();
+        unsafe {
+            self.run_passes_with_settings(
+                ptr_vec.as_mut_ptr(),
+                ptr_vec.len() as u32,
+                codegen_config,
+            )
+        };
+        Ok(())
+    }
+
+    /// Run `num_passes` passes from `passes` on this function, or the default function-level
+    /// optimization passes if `passes` is null.
+    ///
+    /// Panics with Binaryen's message if a pass hits a fatal error, see the [`Module`] docs.
+    unsafe fn run_passes_with_settings(
+        &mut self,
+        passes: *mut *const c_char,
+        num_passes: u32,
+        codegen_config: &CodegenConfig,
+    ) {
+        let inline_size =
+            |size: Option<u32>| size.map_or(-1, |size| size.min(i32::MAX as u32) as i32);
+        let error = binaryen_sys::BinaryenShimFunctionRunPassesWithSettings(
+            self.raw,
+            self.module.inner.raw,
+            passes,
+            num_passes,
+            codegen_config.shrink_level as i32,
+            codegen_config.optimization_level as i32,
+            codegen_config.debug_info as i32,
+            codegen_config.zero_filled_memory as i32,
+            inline_size(codegen_config.always_inline_max_size),
+            inline_size(codegen_config.flexible_inline_max_size),
+            inline_size(codegen_config.one_caller_inline_max_size),
+            codegen_config.closed_world as i32,
+        );
+        if let Some(message) = take_shim_string(error) {
+            panic!("Binaryen hit a fatal error: {}", message.trim_end());
+        }
+    }
+}
+
+impl Module {
+    /// Look up a function by its name.
+    pub fn get_function(&self, name: &str) -> Option<Function<'_>> {
+        self.get_raw_function(name).map(Function::from_raw)
+    }
+
+    /// Look up a function by its name, to change it.
+    pub fn get_function_mut(&mut self, name: &str) -> Option<FunctionMut<'_>> {
+        let raw = self.get_raw_function(name)?;
+        Some(FunctionMut { module: self, raw })
+    }
+
+    fn get_raw_function(&self, name: &str) -> Option<binaryen_sys::BinaryenFunctionRef> {
+        let name = CString::new(name).ok()?;
+        let raw = unsafe { binaryen_sys::BinaryenGetFunction(self.inner.raw, name.as_ptr()) };
+        if raw.is_null() {
+            None
+        } else {
+            Some(raw)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ty, ValueTy};
+
+    #[test]
+    fn test_set_body() {
+        let input = wat::parse_str(
+            r#"
+            (module
+                (func $answer (export "answer") (result i32)
+                    (i32.const 41)
+                )
+            )
+            "#,
+        )
+        .unwrap();
+        let mut module = Module::read(&input).unwrap();
+        assert!(module.get_function("missing").is_none());
+        assert!(module.get_function_mut("missing").is_none());
+
+        let function = module.get_function("answer").unwrap();
+        assert_eq!(function.name(), "answer");
+        assert_eq!(function.body().to_text(), "(i32.const 41)");
+
+        // Wrap the old body in a `select` that doesn't change its value.
+        let old_body = module.get_function_mut("answer").unwrap().take_body();
+        let body = module.get_function("answer").unwrap().body();
+        assert_eq!(body.to_text(), "(unreachable)");
+        let new_body = module.select(module.i32_const(1), old_body, module.i32_const(0), None);
+        let mut function = module.get_function_mut("answer").unwrap();
+        function.set_body(new_body);
+        let body = function.as_function().body().to_text();
+        assert!(body.starts_with("(select"), "{}", body);
+        assert!(body.contains("(i32.const 41)"), "{}", body);
+        module.assert_valid();
+    }
+
+    #[test]
+    #[should_panic(expected = "expression belongs to another module")]
+    fn test_set_body_from_other_module() {
+        let mut module = Module::new();
+        let body = module.nop();
+        module.add_fn("f", Ty::none(), Ty::none(), &[], body);
+
+        let other = Module::new();
+        let body = other.nop();
+        module.get_function_mut("f").unwrap().set_body(body);
+    }
+
+    #[test]
+    fn test_optimize() {
+        let input = wat::parse_str(
+            r#"
+            (module
+                (func $folded (export "folded") (result i32)
+                    (i32.add (i32.const 1) (i32.const 2))
+                )
+                (func $untouched (export "untouched") (result i32)
+                    (i32.add (i32.const 3) (i32.const 4))
+                )
+            )
+            "#,
+        )
+        .unwrap();
+        let mut module = Module::read(&input).unwrap();
+        let config = CodegenConfig {
+            optimization_level: 2,
+            ..CodegenConfig::default()
+        };
+
+        module.get_function_mut("folded").unwrap().optimize(&config);
+        module.assert_valid();
+        let folded = module.get_function("folded").unwrap().body().to_text();
+        assert_eq!(folded, "(i32.const 3)");
+        let untouched = module.get_function("untouched").unwrap().body().to_text();
+        assert!(untouched.starts_with("(i32.add"), "{}", untouched);
+
+        // Change the function and re-optimize just it, as in an edit-compile loop.
+        let new_body = module.select(
+            module.i32_const(1),
+            module.i32_const(42),
+            module.i32_const(0),
+            None,
+        );
+        let mut function = module.get_function_mut("folded").unwrap();
+        function.set_body(new_body);
+        function.optimize(&config);
+        assert_eq!(function.as_function().body().to_text(), "(i32.const 42)");
+        module.assert_valid();
+    }
+
+    #[test]
+    fn test_run_passes() {
+        let input = wat::parse_str(
+            r#"
+            (module
+                (func $hot (export "hot") (local i32)
+                    (local.set 0 (i32.const 1))
+                    (nop)
+                )
+                (func $cold (export "cold") (nop) (nop))
+            )
+            "#,
+        )
+        .unwrap();
+        let mut module = Module::read(&input).unwrap();
+        let config = CodegenConfig::default();
+
+        let mut hot = module.get_function_mut("hot").unwrap();
+        hot.run_passes(&["simplify-locals", "vacuum"], &config)
+            .unwrap();
+        let err = hot.run_passes(&["vacuum", "invalid"], &config).unwrap_err();
+        assert_eq!(err.pass(), "invalid");
+        assert!(matches!(err, FunctionPassError::InvalidPass(_)));
+
+        // Whole-module passes are rejected before anything runs, so `vacuum` leaves the
+        // `nop`s of `cold` alone.
+        let mut cold = module.get_function_mut("cold").unwrap();
+        let err = cold
+            .run_passes(&["vacuum", "inlining"], &config)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            FunctionPassError::NotFunctionParallel("inlining".to_string())
+        );
+
+        module.assert_valid();
+        let body = module.get_function("hot").unwrap().body().to_text();
+        assert!(!body.contains("local.set"), "{}", body);
+        let cold = module.get_function("cold").unwrap().body().to_text();
+        assert_eq!(cold.matches("(nop)").count(), 2, "{}", cold);
+    }
+
+    #[test]
+    fn test_signature() {
+        let input = wat::parse_str(
+            r#"
+            (module
+                (import "env" "log" (func $log (param f64)))
+                (func $f (export "f") (param i32 i64) (result f32) (local f64 v128)
+                    (f32.const 0)
+                )
+                (func $g (export "g") (local funcref))
+            )
+            "#,
+        )
+        .unwrap();
+        let module = Module::read(&input).unwrap();
+
+        let f = module.get_function("f").unwrap();
+        assert_eq!(f.params(), Ty::tuple(&[ValueTy::I32, ValueTy::I64]));
+        assert_eq!(f.results(), Ty::value(ValueTy::F32));
+        assert_eq!(f.num_locals(), 4);
+        let locals: Vec<_> = (0..5).map(|index| f.local_type(index)).collect();
+        assert_eq!(
+            locals,
+            [
+                Some(ValueTy::I32),
+                Some(ValueTy::I64),
+                Some(ValueTy::F64),
+                Some(ValueTy::V128),
+                None
+            ]
+        );
+
+        let log = module.get_function("log").unwrap();
+        assert_eq!(log.params(), Ty::value(ValueTy::F64));
+        assert_eq!(log.results(), Ty::none());
+        assert_eq!(log.num_locals(), 1);
+
+        let g = module.get_function("g").unwrap();
+        assert_eq!(g.num_locals(), 1);
+        assert_eq!(g.local_type(0), None);
+    }
+
+    #[test]
+    fn test_add_fn_returns_function() {
+        let mut module = Module::new();
+        let body = module.nop();
+        let function = module.add_fn("f", Ty::none(), Ty::none(), &[ValueTy::I32], body);
+        assert_eq!(function.name(), "f");
+    }
+}
@@ -0,0 +1,261 @@
+use crate::encoding::read_leb128_u32;
+use crate::{check_preamble, Module, ReadError, WASM_PREAMBLE};
+
+/// The kind of a section of a wasm binary.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SectionKind {
+    /// A custom section with the given name, e.g. `name` or `producers`.
+    Custom(String),
+    Type,
+    Import,
+    Function,
+    Table,
+    Memory,
+    Global,
+    Export,
+    Start,
+    Element,
+    Code,
+    Data,
+    DataCount,
+    Tag,
+    /// A section id this crate doesn't know about.
+    Unknown(u8),
+}
+
+impl SectionKind {
+    fn from_id(id: u8) -> SectionKind {
+        match id {
+            1 => SectionKind::Type,
+            2 => SectionKind::Import,
+            3 => SectionKind::Function,
+            4 => SectionKind::Table,
+            5 => SectionKind::Memory,
+            6 => SectionKind::Global,
+            7 => SectionKind::Export,
+            8 => SectionKind::Start,
+            9 => SectionKind::Element,
+            10 => SectionKind::Code,
+            11 => SectionKind::Data,
+            12 => SectionKind::DataCount,
+            13 => SectionKind::Tag,
+            id => SectionKind::Unknown(id),
+        }
+    }
+}
+
+/// A section of a wasm binary, see [`Header`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Section {
+    pub kind: SectionKind,
+    /// The offset of the section contents from the start of the binary. For custom sections,
+    /// the contents include the section name.
+    pub offset: usize,
+    /// The size of the section contents in bytes.
+    pub size: usize,
+}
+
+/// The layout of a wasm binary, as reported by [`Module::read_header`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Header {
+    /// The sections of the binary, in the order they appear in.
+    pub sections: Vec<Section>,
+}
+
+impl Header {
+    /// Find the first section of the given kind.
+    pub fn section(&self, kind: &SectionKind) -> Option<&Section> {
+        self.sections.iter().find(|section| section.kind == *kind)
+    }
+}
+
+/// How an entry of the `target_features` section declares its feature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FeaturePolicy {
+    /// `+`: the module uses the feature.
+    Used,
+    /// `-`: the module must not be linked with modules using the feature.
+    Disallowed,
+    /// `=`: all modules linked with this one must use the feature.
+    Required,
+}
+
+/// An entry of the `target_features` section, see [`Module::read_target_features`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TargetFeature {
+    pub policy: FeaturePolicy,
+    /// The name of the feature as written in the section, e.g. `simd128`. It need not be one
+    /// Binaryen knows about, see [`Feature::target_feature_name`](crate::Feature).
+    pub name: String,
+}
+
+/// Read a length-prefixed name from the start of `bytes`, returning it along with the number
+/// of bytes it took.
+fn read_name(bytes: &[u8]) -> Result<(String, usize), ReadError> {
+    let (name_len, len) = read_leb128_u32(bytes).ok_or(ReadError::Malformed)?;
+    let name = bytes
+        .get(len..len + name_len as usize)
+        .ok_or(ReadError::Malformed)?;
+    let name = String::from_utf8(name.to_vec()).map_err(|_| ReadError::Malformed)?;
+    Ok((name, len + name_len as usize))
+}
+
+impl Module {
+    /// List the sections of a binary wasm module without parsing it.
+    ///
+    /// Only the section headers and the names of custom sections are looked at, so this is much
+    /// cheaper than [`Module::read`] for large modules, but says nothing about whether the
+    /// section contents are valid.
+    pub fn read_header(module: &[u8]) -> Result<Header, ReadError> {
+        check_preamble(module)?;
+
+        let mut sections = vec![];
+        let mut pos = WASM_PREAMBLE.len();
+        while pos < module.len() {
+            let id = module[pos];
+            pos += 1;
+            let (size, len) = read_leb128_u32(&module[pos..]).ok_or(ReadError::Malformed)?;
+            pos += len;
+            let size = size as usize;
+            let contents = module.get(pos..pos + size).ok_or(ReadError::Malformed)?;
+
+            let kind = if id == 0 {
+                SectionKind::Custom(read_name(contents)?.0)
+            } else {
+                SectionKind::from_id(id)
+            };
+            sections.push(Section {
+                kind,
+                offset: pos,
+                size,
+            });
+            pos += size;
+        }
+        Ok(Header { sections })
+    }
+
+    /// Parse the `target_features` custom section of a binary wasm module, in which toolchains
+    /// declare the features the module was compiled with.
+    ///
+    /// The entries are returned as written, including disallowed features and names Binaryen
+    /// doesn't know. Returns `None` if the binary has no such section. Like
+    /// [`Module::read_header`], the rest of the module isn't parsed.
+    pub fn read_target_features(module: &[u8]) -> Result<Option<Vec<TargetFeature>>, ReadError> {
+        let header = Module::read_header(module)?;
+        let kind = SectionKind::Custom("target_features".to_string());
+        let section = match header.section(&kind) {
+            Some(section) => section,
+            None => return Ok(None),
+        };
+        let contents = &module[section.offset..section.offset + section.size];
+        let (_, mut pos) = read_name(contents)?;
+
+        let (count, len) = read_leb128_u32(&contents[pos..]).ok_or(ReadError::Malformed)?;
+        pos += len;
+        let mut features = vec![];
+        for _ in 0..count {
+            let policy = match contents.get(pos) {
+                Some(b'+') => FeaturePolicy::Used,
+                Some(b'-') => FeaturePolicy::Disallowed,
+                Some(b'=') => FeaturePolicy::Required,
+                _ => return Err(ReadError::Malformed),
+            };
+            let (name, len) = read_name(&contents[pos + 1..])?;
+            pos += 1 + len;
+            features.push(TargetFeature { policy, name });
+        }
+        Ok(Some(features))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::{self, append_custom_section};
+
+    #[test]
+    fn test_read_header() {
+        const CODE: &'static str = r#"
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (func (export "main") (call $log (i32.const 1)))
+            )
+        "#;
+        let mut binary = wat::parse_str(CODE).unwrap();
+        append_custom_section(&mut binary, "producers", &[0]);
+
+        let header = Module::read_header(&binary).unwrap();
+        let kinds: Vec<_> = header
+            .sections
+            .iter()
+            .map(|section| section.kind.clone())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                SectionKind::Type,
+                SectionKind::Import,
+                SectionKind::Function,
+                SectionKind::Export,
+                SectionKind::Code,
+                SectionKind::Custom("producers".to_string()),
+            ]
+        );
+
+        let producers = header
+            .section(&SectionKind::Custom("producers".to_string()))
+            .unwrap();
+        assert_eq!(producers.offset + producers.size, binary.len());
+        assert_eq!(binary[producers.offset + 1..][..9], *b"producers");
+    }
+
+    #[test]
+    fn test_read_target_features() {
+        let mut binary = wat::parse_str("(module)").unwrap();
+        assert_eq!(Module::read_target_features(&binary), Ok(None));
+
+        let mut payload = vec![];
+        encoding::write_leb128_u32(&mut payload, 3);
+        for (prefix, name) in [(b'+', "simd128"), (b'-', "atomics"), (b'=', "made-up")] {
+            payload.push(prefix);
+            encoding::write_name(&mut payload, name.as_bytes());
+        }
+        append_custom_section(&mut binary, "target_features", &payload);
+
+        let feature = |policy, name: &str| TargetFeature {
+            policy,
+            name: name.to_string(),
+        };
+        assert_eq!(
+            Module::read_target_features(&binary),
+            Ok(Some(vec![
+                feature(FeaturePolicy::Used, "simd128"),
+                feature(FeaturePolicy::Disallowed, "atomics"),
+                feature(FeaturePolicy::Required, "made-up"),
+            ]))
+        );
+
+        // An entry with an unknown prefix.
+        let mut binary = wat::parse_str("(module)").unwrap();
+        append_custom_section(&mut binary, "target_features", b"\x01*\x01a");
+        assert_eq!(
+            Module::read_target_features(&binary),
+            Err(ReadError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_read_header_errors() {
+        assert_eq!(Module::read_header(b"\0asm"), Err(ReadError::TooShort));
+        assert_eq!(Module::read_header(b"\0wasm"), Err(ReadError::BadMagic));
+        assert_eq!(
+            Module::read_header(b"\0asm\x01\0\0\0"),
+            Ok(Header::default())
+        );
+        // A type section claiming more bytes than there are.
+        assert_eq!(
+            Module::read_header(b"\0asm\x01\0\0\0\x01\x05\0"),
+            Err(ReadError::Malformed)
+        );
+    }
+}
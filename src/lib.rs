@@ -1,13 +1,61 @@
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::str::FromStr;
-use std::{ptr, slice};
+use std::{error, fmt, fs, io, ptr, slice};
 
+mod builder;
+mod compression;
+mod diagnostics;
+mod encoding;
+mod exports;
+mod expr;
+mod features;
+mod function;
+mod header;
+mod literal;
+mod metrics;
+mod ops;
+mod passes;
+mod pipeline;
+pub mod prelude;
+mod relooper;
+mod table;
 pub mod tools;
+mod types;
+
+pub use builder::ModuleBuilder;
+pub use compression::{Compression, ReadCompressedError};
+pub use diagnostics::{reset_diagnostic_handler, set_diagnostic_handler};
+pub use exports::{Export, ExternalKind};
+pub use expr::{Expr, ExprRef, Segment, SegmentKind};
+pub use features::{Feature, Features, ParseFeatureError};
+pub use function::{Function, FunctionMut, FunctionPassError};
+pub use header::{FeaturePolicy, Header, Section, SectionKind, TargetFeature};
+pub use literal::Literal;
+pub use metrics::{MetricsDiff, ModuleMetrics};
+pub use ops::UnaryOp;
+pub use passes::{AsyncifyOptions, ExtractFunctionError, LimitSegmentsError, NameMap};
+pub use pipeline::{InvalidPassError, OptimizeProfile, PassPipeline};
+pub use relooper::{Block, BlockId, PlainBlock, Relooper, RelooperError, SwitchBlock};
+pub use table::{ElementSegment, Table};
+pub use types::{Ty, ValueTy};
 
 /// Codegen configuration.
-#[derive(Default)]
+///
+/// New fields may be added in the future, so the configuration can't be built with a struct
+/// literal outside of this crate. Start from [`CodegenConfig::default`] or
+/// [`OptLevel::to_codegen_config`] and set the fields instead:
+///
+/// ```
+/// let mut config = binaryen::OptLevel::Os.to_codegen_config();
+/// config.debug_info = true;
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct CodegenConfig {
     /// 0, 1, 2 correspond to -O0, -Os, -Oz
     pub shrink_level: u32,
@@ -15,10 +63,445 @@ pub struct CodegenConfig {
     pub optimization_level: u32,
     /// If set, the names section is emitted.
     pub debug_info: bool,
+    /// If set, memory is assumed to be zero-filled when the module is instantiated, even if
+    /// it's imported. This lets passes drop the zeros from data segments.
+    ///
+    /// Like the other settings, this only applies to the passes run with this configuration
+    /// and doesn't change Binaryen's global default.
+    pub zero_filled_memory: bool,
+    /// If set, the `reorder-functions-by-name` and `reorder-globals` passes run after the
+    /// passes of [`Module::optimize`] and [`Module::run_optimization_passes`], as the last step.
+    /// This may cost a few bytes compared to the order the optimizer left.
+    ///
+    /// Binaryen's passes already produce the same output for the same input, configuration and
+    /// Binaryen version, even though they run on several threads, so there are no heuristics
+    /// to turn off and this setting doesn't change what the other passes do. What it adds is
+    /// that the order of the functions doesn't depend on the order they were added in, e.g.
+    /// with [`Module::add_fn`] or [`Module::merge`]. Globals are sorted by use count, which
+    /// keeps globals that are used equally often in the order they had.
+    ///
+    /// The per-function methods of [`FunctionMut`] ignore this setting, as they don't reorder
+    /// anything.
+    pub deterministic: bool,
+    /// Functions up to this size are always inlined, like `wasm-opt`'s
+    /// `--always-inline-max-function-size`. `None` keeps Binaryen's default.
+    ///
+    /// Sizes are measured in instructions. Inlining only happens in the `inlining` and
+    /// `inlining-optimizing` passes, which the default passes include from -O2 on.
+    pub always_inline_max_size: Option<u32>,
+    /// Functions up to this size are inlined where the inliner thinks it pays off, when not
+    /// optimizing for size, like `--flexible-inline-max-function-size`.
+    pub flexible_inline_max_size: Option<u32>,
+    /// Functions with a single caller are inlined up to this size, like
+    /// `--one-caller-inline-max-function-size`.
+    pub one_caller_inline_max_size: Option<u32>,
+    /// If set, passes may assume that no code outside of the module sees its GC types, except
+    /// through the exports, like `wasm-opt`'s `--closed-world`. This enables the type-level
+    /// GC optimizations, see [`Module::gc_optimize`].
+    pub closed_world: bool,
+}
+
+impl CodegenConfig {
+    /// The highest meaningful `shrink_level`, corresponding to -Oz.
+    pub const MAX_SHRINK_LEVEL: u32 = 2;
+    /// The highest meaningful `optimization_level`, corresponding to -O4.
+    pub const MAX_OPTIMIZATION_LEVEL: u32 = 4;
+}
+
+/// The optimization levels of `wasm-opt`.
+///
+/// Note that -Os and -Oz are not levels of their own, but shorthands for -O2 with a shrink
+/// level of 1 and 2 respectively.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+    O3,
+    O4,
+    Os,
+    Oz,
+}
+
+impl OptLevel {
+    /// The `(shrink_level, optimization_level)` pair this level stands for.
+    pub fn levels(self) -> (u32, u32) {
+        match self {
+            OptLevel::O0 => (0, 0),
+            OptLevel::O1 => (0, 1),
+            OptLevel::O2 => (0, 2),
+            OptLevel::O3 => (0, 3),
+            OptLevel::O4 => (0, 4),
+            OptLevel::Os => (1, 2),
+            OptLevel::Oz => (2, 2),
+        }
+    }
+
+    /// A codegen configuration for this level, with the rest of the settings left at their
+    /// defaults.
+    pub fn to_codegen_config(self) -> CodegenConfig {
+        let (shrink_level, optimization_level) = self.levels();
+        CodegenConfig {
+            shrink_level,
+            optimization_level,
+            ..CodegenConfig::default()
+        }
+    }
 }
 
+/// Check whether `pass` is one of the passes listed by [`available_passes`].
+///
+/// Passes Binaryen only registers for its own tests are hidden from that list and rejected
+/// here as well.
 fn is_valid_pass(pass: &str) -> bool {
-    binaryen_sys::passes::OptimizationPass::from_str(pass).is_ok()
+    if binaryen_sys::passes::OptimizationPass::from_str(pass).is_ok() {
+        return true;
+    }
+    // The generated list may miss passes whose registration it couldn't make sense of, so ask
+    // the registry of the linked Binaryen.
+    match CString::new(pass) {
+        Ok(pass) => unsafe {
+            binaryen_sys::BinaryenShimIsPassRegistered(pass.as_ptr()) == 1
+                && binaryen_sys::BinaryenShimIsPassHidden(pass.as_ptr()) == 0
+        },
+        Err(_) => false,
+    }
+}
+
+/// An optimization pass registered in Binaryen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PassInfo {
+    /// The name used to run the pass, e.g. `"vacuum"`.
+    pub name: String,
+    /// A short description of what the pass does.
+    pub description: String,
+}
+
+/// List the optimization passes supported by the linked Binaryen, in alphabetical order.
+///
+/// Unlike [`passes_with_prefix`], which relies on the list of passes extracted at build time, this
+/// asks Binaryen's pass registry at runtime. Internal passes meant for testing Binaryen are not
+/// included.
+pub fn available_passes() -> Vec<PassInfo> {
+    let mut passes = vec![];
+    unsafe {
+        for index in 0..binaryen_sys::BinaryenShimGetNumRegisteredPasses() {
+            let raw_name = binaryen_sys::BinaryenShimGetRegisteredPassName(index);
+            let name = match take_shim_string(raw_name) {
+                Some(name) => name,
+                None => continue,
+            };
+            let c_name = CString::new(name.as_str()).unwrap();
+            if binaryen_sys::BinaryenShimIsPassHidden(c_name.as_ptr()) == 1 {
+                continue;
+            }
+            let description = take_shim_string(binaryen_sys::BinaryenShimGetPassDescription(
+                c_name.as_ptr(),
+            ))
+            .unwrap_or_default();
+            passes.push(PassInfo { name, description });
+        }
+    }
+    passes
+}
+
+/// Takes ownership of a string allocated by the shim.
+unsafe fn take_shim_string(raw: *mut c_char) -> Option<String> {
+    if raw.is_null() {
+        return None;
+    }
+    let string = CStr::from_ptr(raw).to_string_lossy().into_owned();
+    binaryen_sys::BinaryenShimDisposeString(raw);
+    Some(string)
+}
+
+/// Borrow a name owned by Binaryen.
+///
+/// Binaryen only accepts names that are valid UTF-8.
+unsafe fn name_from_raw<'a>(raw: *const c_char) -> &'a str {
+    CStr::from_ptr(raw)
+        .to_str()
+        .expect("Binaryen names are valid UTF-8")
+}
+
+/// Error returned when a module doesn't pass validation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    message: String,
+}
+
+impl ValidationError {
+    /// Diagnostics reported by the Binaryen validator.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "module is invalid: {}", self.message)
+    }
+}
+
+impl error::Error for ValidationError {}
+
+/// Iterate over the names of all the optimization passes that start with `prefix`.
+///
+/// The list comes from the Binaryen pass registry, so it reflects the passes of the bundled
+/// Binaryen version.
+///
+/// ```
+/// let simplify_passes: Vec<_> = binaryen::passes_with_prefix("simplify-").collect();
+/// assert!(simplify_passes.contains(&"simplify-locals"));
+/// ```
+pub fn passes_with_prefix(prefix: &str) -> impl Iterator<Item = &'static str> + '_ {
+    binaryen_sys::passes::PASS_NAMES
+        .iter()
+        .copied()
+        .filter(move |name| name.starts_with(prefix))
+}
+
+/// The preamble every wasm binary starts with: the magic number followed by the version.
+const WASM_PREAMBLE: &[u8; 8] = b"\0asm\x01\0\0\0";
+
+/// The comment [`Module::write_text`] records the enabled features in, as there is no place for
+/// them in the text format itself.
+const FEATURES_COMMENT: &str = ";; features: ";
+
+/// Check that `module` starts with [`WASM_PREAMBLE`].
+fn check_preamble(module: &[u8]) -> Result<(), ReadError> {
+    let preamble_len = module.len().min(WASM_PREAMBLE.len());
+    if module[..preamble_len] != WASM_PREAMBLE[..preamble_len] {
+        return Err(ReadError::BadMagic);
+    }
+    if module.len() < WASM_PREAMBLE.len() {
+        return Err(ReadError::TooShort);
+    }
+    Ok(())
+}
+
+/// Error returned by [`Module::read`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadError {
+    /// The input ends before the 8-byte preamble (magic number and version). More bytes may
+    /// turn it into a valid module.
+    TooShort,
+    /// The input doesn't start with the wasm magic number and version 1.
+    BadMagic,
+    /// The preamble is fine, but Binaryen failed to parse the rest of the module.
+    Malformed,
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::TooShort => write!(f, "module is too short"),
+            ReadError::BadMagic => write!(f, "bad magic number or version"),
+            ReadError::Malformed => write!(f, "malformed module"),
+        }
+    }
+}
+
+impl error::Error for ReadError {}
+
+/// Error returned when parsing the text format fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    message: String,
+}
+
+impl ParseError {
+    /// Description of the problem reported by the Binaryen parser.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "parse error: {}", self.message)
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// Parse the text format and print it back in Binaryen's canonical form.
+///
+/// The normalization is semantic, not textual: comments are dropped, formatting and
+/// abbreviations are replaced by Binaryen's own. This makes it suitable for golden-file tests
+/// that shouldn't break on whitespace changes.
+///
+/// ```
+/// let a = binaryen::normalize_wat("(module (func $f (nop)))").unwrap();
+/// let b = binaryen::normalize_wat("(module\n  ;; a comment\n  (func $f\n    nop))").unwrap();
+/// assert_eq!(a, b);
+/// ```
+pub fn normalize_wat(wat: &str) -> Result<String, ParseError> {
+    Ok(Module::parse_text(wat)?.write_text())
+}
+
+/// Optimize the module stored at `input` and write the result to `output`.
+///
+/// The module is validated before optimizing. Both an unreadable and an invalid module are
+/// reported as [`io::ErrorKind::InvalidData`] errors. The names section is written if
+/// `codegen_config.debug_info` is set.
+pub fn optimize_file(
+    input: &Path,
+    output: &Path,
+    codegen_config: &CodegenConfig,
+) -> io::Result<()> {
+    let contents = fs::read(input)?;
+    let mut module =
+        Module::read(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    module
+        .validate()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    module.optimize(codegen_config);
+    fs::write(output, module.write_with_config(codegen_config))
+}
+
+/// Error returned by [`Module::merge`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MergeError {
+    /// Both modules define a function, global, tag, memory, table or segment with this name.
+    DuplicateName(String),
+    /// Both modules export something under this name.
+    DuplicateExport(String),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeError::DuplicateName(name) => write!(f, "duplicate name: {}", name),
+            MergeError::DuplicateExport(name) => write!(f, "duplicate export: {}", name),
+        }
+    }
+}
+
+impl error::Error for MergeError {}
+
+/// Error returned by [`Module::run_passes_validated`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransformError {
+    /// One of the passes is not known to Binaryen. No passes were run.
+    InvalidPass(InvalidPassError),
+    /// The passes ran, but the resulting module is invalid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for TransformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransformError::InvalidPass(err) => err.fmt(f),
+            TransformError::Invalid(err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for TransformError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TransformError::InvalidPass(err) => Some(err),
+            TransformError::Invalid(err) => Some(err),
+        }
+    }
+}
+
+impl From<InvalidPassError> for TransformError {
+    fn from(err: InvalidPassError) -> TransformError {
+        TransformError::InvalidPass(err)
+    }
+}
+
+impl From<ValidationError> for TransformError {
+    fn from(err: ValidationError) -> TransformError {
+        TransformError::Invalid(err)
+    }
+}
+
+/// Error returned by [`Module::read_validated`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReadValidatedError {
+    /// The bytes are not a module, see [`Module::read`].
+    Read(ReadError),
+    /// The module was read, but is invalid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for ReadValidatedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadValidatedError::Read(err) => err.fmt(f),
+            ReadValidatedError::Invalid(err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for ReadValidatedError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ReadValidatedError::Read(err) => Some(err),
+            ReadValidatedError::Invalid(err) => Some(err),
+        }
+    }
+}
+
+impl From<ReadError> for ReadValidatedError {
+    fn from(err: ReadError) -> ReadValidatedError {
+        ReadValidatedError::Read(err)
+    }
+}
+
+impl From<ValidationError> for ReadValidatedError {
+    fn from(err: ValidationError) -> ReadValidatedError {
+        ReadValidatedError::Invalid(err)
+    }
+}
+
+/// How to emit a source map, see [`Module::write_with_source_map_mode`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SourceMapMode {
+    /// Don't emit a source map.
+    None,
+    /// Return the source map separately. The module refers to it by the given URL.
+    External(String),
+    /// Embed the source map into the module as a `data:` URL.
+    Inline,
+}
+
+/// What [`Module::optimize_with_outcome`] did to a module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OptimizeOutcome {
+    /// Whether the serialized module changed. `false` means the module was already optimal as
+    /// far as the passes are concerned.
+    pub changed: bool,
+    /// The size of the serialized module before optimizing.
+    pub before_bytes: usize,
+    /// The size of the serialized module after optimizing.
+    pub after_bytes: usize,
+}
+
+/// How to serialize a module, see [`Module::write_with_options`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Emit the names section, like `wasm-opt -g`.
+    pub debug_info: bool,
+    /// Write the symbol map of the module to this file, like `wasm-opt --symbolmap`. See
+    /// [`Module::symbol_map`].
+    pub symbol_map: Option<PathBuf>,
+    /// Emit a source map, which the module refers to by this URL, like
+    /// `wasm-opt --output-source-map-url`.
+    pub source_map_url: Option<String>,
+}
+
+/// Convert a source map URL given by the user for Binaryen.
+fn source_map_url(url: &str) -> io::Result<CString> {
+    CString::new(url).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "source map URL contains a NUL byte",
+        )
+    })
 }
 
 struct InnerModule {
@@ -32,16 +515,116 @@ impl Drop for InnerModule {
 }
 
 /// Modules contain lists of functions, imports, exports, function types.
+///
+/// A `Module` is neither `Send` nor `Sync`: handles derived from it share ownership of the
+/// underlying Binaryen module through an `Rc`. To move a module to another thread, convert
+/// it into an [`OwnedModule`] with [`Module::into_owned`].
+///
+/// # Copies
+///
+/// `Module` deliberately doesn't implement `Clone`. A cloned handle would share the underlying
+/// Binaryen module, so optimizing one "copy" would change the other as well. Use
+/// [`Module::deep_clone`] for an independent copy:
+///
+/// ```compile_fail
+/// let module = binaryen::Module::new();
+/// let copy = module.clone();
+/// ```
+///
+/// To share a single module between threads, wrap the [`OwnedModule`] in an
+/// `Arc<Mutex<OwnedModule>>`, which makes the sharing and the need to lock for every change
+/// explicit.
+///
+/// # Fatal errors
+///
+/// Binaryen reports some problems, such as running a pass on a module it doesn't support, as
+/// fatal errors. What happens to them depends on the call:
+///
+/// - Reading and parsing, e.g. [`Module::read`] and [`Module::parse_text`], return them as
+///   `Err`s.
+/// - Running passes, e.g. with [`Module::optimize`], and writing binaries, e.g. with
+///   [`Module::write`], turn them into panics with Binaryen's message, which can be caught
+///   with [`std::panic::catch_unwind`]. This only covers errors hit on the calling thread.
+///   Function-parallel passes, which are most of the optimization passes, work on the
+///   functions on Binaryen's worker threads, and a fatal error there ends the process through
+///   `std::terminate` instead. To have all passes run on the calling thread, so that their
+///   fatal errors become panics as well, set the `BINARYEN_CORES` environment variable to `1`
+///   before the first pass runs, at the cost of running them on a single core.
+/// - All other calls into Binaryen abort the process with the message on stderr: validation,
+///   merging, copying, fuzzing, renaming, printing expressions and looking up passes, imports
+///   or types. The methods that call Binaryen's C API directly, such as the expression
+///   builders, [`Module::add_fn`] and [`Module::add_global`], first check what would make it
+///   hit a fatal error, e.g. duplicate names, and panic themselves instead.
+///
+/// Passes panic instead of returning a `Result` on purpose. The fatal errors they hit come
+/// from modules Binaryen doesn't support or from bugs of Binaryen, not from conditions a caller
+/// can handle, and they may leave the module half transformed, so it should be dropped rather
+/// than used further. An embedder that must not go down, such as an optimization service, can
+/// catch the panic and report the input as failed, or, given the limit on worker threads
+/// above, run the optimizer in a separate process.
+///
+/// Binaryen's internal assertions are not covered by any of this and abort the process, so only
+/// validated modules should be given to the optimizer: see [`Module::validate`].
+///
+/// The panics of this crate itself, e.g. for names containing NUL bytes, happen before calling
+/// into Binaryen or after it returned, and never unwind through Binaryen code. A module used in
+/// a panicking call stays consistent and is still disposed of when dropped.
 pub struct Module {
     inner: Rc<InnerModule>,
 }
 
+/// A uniquely owned module that can be sent to other threads.
+///
+/// Binaryen modules have no thread affinity, so a module that nothing else refers to can be
+/// moved between threads freely. `OwnedModule` is only a transport handle: convert it back
+/// with [`OwnedModule::into_module`] to work with it.
+///
+/// ```
+/// # let module = binaryen::Module::read(b"\0asm\x01\0\0\0").unwrap();
+/// let owned = module.into_owned().ok().unwrap();
+/// let handle = std::thread::spawn(move || {
+///     let mut module = owned.into_module();
+///     module.optimize(&binaryen::CodegenConfig::default());
+///     module.into_owned().ok().unwrap()
+/// });
+/// let module = handle.join().unwrap().into_module();
+/// ```
+pub struct OwnedModule {
+    inner: InnerModule,
+}
+
+// The underlying module is uniquely owned by `OwnedModule`.
+unsafe impl Send for OwnedModule {}
+
+impl OwnedModule {
+    /// Create an independent copy of the module, see [`Module::deep_clone`].
+    pub fn deep_clone(&self) -> OwnedModule {
+        OwnedModule {
+            inner: InnerModule {
+                raw: unsafe { binaryen_sys::BinaryenShimModuleCopy(self.inner.raw) },
+            },
+        }
+    }
+
+    /// Turn this back into a regular `Module` on the current thread.
+    pub fn into_module(self) -> Module {
+        Module {
+            inner: Rc::new(self.inner),
+        }
+    }
+}
+
+impl Default for Module {
+    fn default() -> Module {
+        Module::new()
+    }
+}
+
 impl Module {
     /// Create a new empty Module.
     ///
-    /// This is not public since all IR-construction related operations were removed from
-    /// Binaryen and thus there is not much sense in creating an empty module.
-    fn new() -> Module {
+    /// Use the expression builders and [`Module::add_fn`] to fill it.
+    pub fn new() -> Module {
         unsafe {
             let raw = binaryen_sys::BinaryenModuleCreate();
             Module::from_raw(raw)
@@ -50,146 +633,1245 @@ impl Module {
 
     /// Deserialize a module from binary form.
     ///
-    /// Returns `Err` if an invalid module is given.
-    pub fn read(module: &[u8]) -> Result<Module, ()> {
+    /// Returns `Err` if an invalid module is given. The preamble is checked before handing the
+    /// input to Binaryen, so a truncated preamble can be told apart from a malformed module,
+    /// see [`ReadError`].
+    ///
+    /// Anything that can be viewed as bytes can be passed, e.g. a `Vec<u8>`, a reference to it or a
+    /// byte array.
+    ///
+    /// ```
+    /// # use binaryen::Module;
+    /// let binary = Module::new().write();
+    /// assert!(Module::read(&binary).is_ok());
+    /// assert!(Module::read(binary).is_ok());
+    /// assert!(Module::read(b"\0asm\x01\0\0\0").is_ok());
+    /// ```
+    pub fn read<B: AsRef<[u8]>>(module: B) -> Result<Module, ReadError> {
+        let module = module.as_ref();
+        check_preamble(module)?;
+
         unsafe {
             let raw = binaryen_sys::BinaryenModuleSafeRead(
                 module.as_ptr() as *const c_char,
                 module.len(),
             );
             if raw.is_null() {
-                return Err(());
+                return Err(ReadError::Malformed);
             }
             Ok(Module::from_raw(raw))
         }
     }
 
-    pub unsafe fn from_raw(raw: binaryen_sys::BinaryenModuleRef) -> Module {
-        Module {
-            inner: Rc::new(InnerModule { raw }),
+    /// Deserialize a module from binary form and validate it, see [`Module::validate`].
+    ///
+    /// [`Module::read`] only checks that the bytes are well-formed, e.g. a call with arguments of
+    /// the wrong type is read just fine. Reading an untrusted module should go through this
+    /// function instead, since Binaryen expects the modules it works on to be valid.
+    pub fn read_validated<B: AsRef<[u8]>>(module: B) -> Result<Module, ReadValidatedError> {
+        let module = Module::read(module)?;
+        module.validate()?;
+        Ok(module)
+    }
+
+    /// Parse a module from the text format.
+    ///
+    /// Returns `Err` with the parser's description of the problem if the text is malformed.
+    /// Note that, like [`Module::read`], this doesn't validate the module.
+    ///
+    /// If the text starts with a `;; features: ...` comment, as written by
+    /// [`Module::write_text`], the listed features are enabled on the module.
+    pub fn parse_text(text: &str) -> Result<Module, ParseError> {
+        let features = match text.trim_start().strip_prefix(FEATURES_COMMENT) {
+            Some(rest) => {
+                let names = rest.lines().next().unwrap_or_default();
+                let features = names.parse::<Features>().map_err(|err| ParseError {
+                    message: err.to_string(),
+                })?;
+                Some(features)
+            }
+            None => None,
+        };
+        let text = CString::new(text).map_err(|_| ParseError {
+            message: "text contains a NUL byte".to_string(),
+        })?;
+        let mut module = unsafe {
+            let mut error = ptr::null_mut();
+            let raw = binaryen_sys::BinaryenModuleSafeParse(text.as_ptr(), &mut error);
+            if let Some(message) = take_shim_string(error) {
+                return Err(ParseError { message });
+            }
+            Module::from_raw(raw)
+        };
+        if let Some(features) = features {
+            module.set_features(features);
+        }
+        Ok(module)
+    }
+
+    /// Parse a module from a file in the text format, see [`Module::parse_text`].
+    ///
+    /// A parse error is reported as an [`io::ErrorKind::InvalidData`] error wrapping the
+    /// [`ParseError`].
+    pub fn from_wat_file(path: &Path) -> io::Result<Module> {
+        let text = fs::read_to_string(path)?;
+        Module::parse_text(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Write the module to a file in the text format, see [`Module::write_text`].
+    pub fn to_wat_file(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.write_text())
+    }
+
+    pub unsafe fn from_raw(raw: binaryen_sys::BinaryenModuleRef) -> Module {
+        Module {
+            inner: Rc::new(InnerModule { raw }),
+        }
+    }
+
+    /// Convert this module into an [`OwnedModule`] that can be sent to other threads.
+    ///
+    /// Returns the module back as `Err` if there are other handles referring to it still alive.
+    pub fn into_owned(self) -> Result<OwnedModule, Module> {
+        match Rc::try_unwrap(self.inner) {
+            Ok(inner) => Ok(OwnedModule { inner }),
+            Err(inner) => Err(Module { inner }),
+        }
+    }
+
+    /// Create an independent copy of the module.
+    ///
+    /// Changes made to the copy don't affect this module and vice versa. Expressions keep
+    /// referring to the module they were created by, not to the copy.
+    pub fn deep_clone(&self) -> Module {
+        unsafe { Module::from_raw(binaryen_sys::BinaryenShimModuleCopy(self.inner.raw)) }
+    }
+
+    /// Check whether two modules have the same contents, regardless of names and encoding.
+    ///
+    /// Both modules are written to binary without the names section, read back, and compared
+    /// in the text format. So modules that only differ in the names of functions, locals or
+    /// labels, or in how Binaryen happened to encode them are equal, while a different
+    /// instruction, order of functions or set of enabled features makes them differ. Custom
+    /// sections are not compared. A module that is too broken to be read back is not equal to
+    /// any module.
+    pub fn semantically_eq(&self, other: &Module) -> bool {
+        match (self.canonical_text(), other.canonical_text()) {
+            (Some(text), Some(other_text)) => text == other_text,
+            _ => false,
+        }
+    }
+
+    /// The text format of the module as read back from its binary without names, see
+    /// [`Module::semantically_eq`].
+    fn canonical_text(&self) -> Option<String> {
+        let mut module = Module::read(&self.write()).ok()?;
+        // The binary only records the features if the module has a `target_features` section.
+        module.set_features(self.features());
+        Some(module.write_text())
+    }
+
+    /// Run the standard optimization passes on the module.
+    ///
+    /// The passes run under the features currently enabled on the module, see
+    /// [`Module::features`]. Unlike `wasm-opt`, which enables all features unless given
+    /// `--mvp-features`, this crate doesn't enable features for you: e.g. a module read from a
+    /// binary has Binaryen's defaults plus the features of its `target_features` section, if
+    /// any. Use [`Module::optimize_all_features`] or [`Module::optimize_mvp`] to pick the
+    /// feature set explicitly.
+    ///
+    /// With the `tracing` feature, the passes run inside a `binaryen::passes` span that records
+    /// how long they took and the size of the module binary before and after.
+    pub fn optimize(&mut self, codegen_config: &CodegenConfig) {
+        self.traced("default", |module| {
+            unsafe { module.run_passes_with_settings(ptr::null_mut(), 0, &[], codegen_config) }
+            module.run_final_passes(codegen_config);
+        });
+    }
+
+    /// Run the standard optimization passes on the module with the given features enabled.
+    ///
+    /// The features are set on the module before optimizing and stay set afterwards, see
+    /// [`Module::set_features`]. This makes the outcome independent of the features the
+    /// module happened to be read or created with.
+    pub fn optimize_with_features(&mut self, codegen_config: &CodegenConfig, features: Features) {
+        self.set_features(features);
+        self.optimize(codegen_config);
+    }
+
+    /// Run the standard optimization passes on the module with all features enabled, like
+    /// `wasm-opt --all-features`.
+    ///
+    /// The passes may then introduce instructions of any proposal, so the result may only run
+    /// on engines that support them. See [`Module::optimize_with_features`].
+    pub fn optimize_all_features(&mut self, codegen_config: &CodegenConfig) {
+        self.optimize_with_features(codegen_config, Features::all());
+    }
+
+    /// Run the standard optimization passes on the module with only the MVP features enabled,
+    /// like `wasm-opt --mvp-features`. See [`Module::optimize_with_features`].
+    pub fn optimize_mvp(&mut self, codegen_config: &CodegenConfig) {
+        self.optimize_with_features(codegen_config, Features::MVP);
+    }
+
+    /// Run the standard optimization passes on the module and report whether they changed it.
+    ///
+    /// The module is serialized before and after optimizing, and `true` is returned if the
+    /// bytes differ. Useful for "optimize until stable" loops.
+    pub fn optimize_and_report(&mut self, codegen_config: &CodegenConfig) -> bool {
+        self.optimize_with_outcome(codegen_config).changed
+    }
+
+    /// Run the standard optimization passes on the module and report what they did.
+    ///
+    /// Like [`Module::optimize_and_report`], but also tells the sizes of the module before and
+    /// after, e.g. for build caches and logs. A module can change without changing its size.
+    pub fn optimize_with_outcome(&mut self, codegen_config: &CodegenConfig) -> OptimizeOutcome {
+        let before = self.write();
+        self.optimize(codegen_config);
+        let after = self.write();
+        OptimizeOutcome {
+            changed: after != before,
+            before_bytes: before.len(),
+            after_bytes: after.len(),
+        }
+    }
+
+    /// Run the standard optimization passes repeatedly until the module stops shrinking, like
+    /// `wasm-opt --converge`.
+    ///
+    /// Stops after `max_iters` rounds at most and returns the number of rounds performed. The
+    /// last round is the one that didn't shrink the module any further, unless the limit was hit.
+    pub fn optimize_converge(&mut self, codegen_config: &CodegenConfig, max_iters: usize) -> usize {
+        let mut size = self.write().len();
+        let mut iterations = 0;
+        while iterations < max_iters {
+            self.optimize(codegen_config);
+            iterations += 1;
+
+            let new_size = self.write().len();
+            if new_size >= size {
+                break;
+            }
+            size = new_size;
+        }
+        iterations
+    }
+
+    /// Run a specified set of optimization passes on the module.
+    ///
+    /// Returns `Err` without running any passes if one of them is not listed by
+    /// [`available_passes`]: passes that Binaryen only registers for its own tests are
+    /// rejected.
+    ///
+    /// With the `tracing` feature, the passes run inside a `binaryen::passes` span like in
+    /// [`Module::optimize`], with the pass names recorded in its `passes` field.
+    pub fn run_optimization_passes<B: AsRef<str>, I: IntoIterator<Item = B>>(
+        &mut self,
+        passes: I,
+        codegen_config: &CodegenConfig,
+    ) -> Result<(), ()> {
+        self.run_optimization_passes_with_arguments(passes, &[], codegen_config)
+    }
+
+    /// Run a specified set of optimization passes on the module, with the given pass arguments
+    /// set for this run only. See [`Module::run_optimization_passes`].
+    pub(crate) fn run_optimization_passes_with_arguments<
+        B: AsRef<str>,
+        I: IntoIterator<Item = B>,
+    >(
+        &mut self,
+        passes: I,
+        arguments: &[(&str, &str)],
+        codegen_config: &CodegenConfig,
+    ) -> Result<(), ()> {
+        let mut cstr_vec: Vec<_> = vec![];
+
+        for pass in passes {
+            if !is_valid_pass(pass.as_ref()) {
+                return Err(());
+            }
+
+            cstr_vec.push(CString::new(pass.as_ref()).unwrap());
+        }
+
+        // NOTE: BinaryenModuleRunPasses expectes a mutable ptr
+        let mut ptr_vec: Vec<_> = cstr_vec.iter().map(|pass| pass.as_ptr()).collect();
+
+        let names: Vec<_> = cstr_vec.iter().map(|pass| pass.to_str().unwrap()).collect();
+        self.traced(&names.join(","), |module| {
+            unsafe {
+                module.run_passes_with_settings(
+                    ptr_vec.as_mut_ptr(),
+                    ptr_vec.len() as u32,
+                    arguments,
+                    codegen_config,
+                )
+            };
+            module.run_final_passes(codegen_config);
+        });
+        Ok(())
+    }
+
+    /// Run `f` on the module inside a `binaryen::passes` span at the info level.
+    ///
+    /// The span records `passes`, the comma separated names of the passes or `default` for the
+    /// standard optimization passes, `duration_us`, the time they took in microseconds, and
+    /// `before_bytes` and `after_bytes`, the size of the module binary before and after. The
+    /// module is only written out to measure it if the span is enabled.
+    #[cfg(feature = "tracing")]
+    fn traced(&mut self, passes: &str, f: impl FnOnce(&mut Module)) {
+        use tracing::field::Empty;
+
+        let span = tracing::info_span!(
+            "binaryen::passes",
+            passes,
+            duration_us = Empty,
+            before_bytes = Empty,
+            after_bytes = Empty,
+        );
+        let _entered = span.enter();
+        if !span.is_disabled() {
+            span.record("before_bytes", self.write().len() as u64);
+        }
+        let start = std::time::Instant::now();
+        f(self);
+        span.record("duration_us", start.elapsed().as_micros() as u64);
+        if !span.is_disabled() {
+            span.record("after_bytes", self.write().len() as u64);
+        }
+    }
+
+    /// Run `f` on the module. Spans are only opened with the `tracing` feature.
+    #[cfg(not(feature = "tracing"))]
+    fn traced(&mut self, _passes: &str, f: impl FnOnce(&mut Module)) {
+        f(self)
+    }
+
+    /// Run `num_passes` passes from `passes`, or the default optimization passes if `passes` is
+    /// null, with `arguments` and the settings of `codegen_config`.
+    ///
+    /// Panics with Binaryen's message if a pass hits a fatal error, see the [`Module`] docs.
+    unsafe fn run_passes_with_settings(
+        &mut self,
+        passes: *mut *const c_char,
+        num_passes: u32,
+        arguments: &[(&str, &str)],
+        codegen_config: &CodegenConfig,
+    ) {
+        let inline_size =
+            |size: Option<u32>| size.map_or(-1, |size| size.min(i32::MAX as u32) as i32);
+        let mut arguments = passes::PassArguments::new(arguments);
+        let error = binaryen_sys::BinaryenModuleRunPassesWithSettings(
+            self.inner.raw,
+            passes,
+            num_passes,
+            arguments.names.as_mut_ptr(),
+            arguments.values.as_mut_ptr(),
+            arguments.names.len() as u32,
+            codegen_config.shrink_level as i32,
+            codegen_config.optimization_level as i32,
+            codegen_config.debug_info as i32,
+            codegen_config.zero_filled_memory as i32,
+            inline_size(codegen_config.always_inline_max_size),
+            inline_size(codegen_config.flexible_inline_max_size),
+            inline_size(codegen_config.one_caller_inline_max_size),
+            codegen_config.closed_world as i32,
+        );
+        if let Some(message) = take_shim_string(error) {
+            panic!("Binaryen hit a fatal error: {}", message.trim_end());
+        }
+    }
+
+    /// Run the passes requested by `codegen_config` to finish off any other passes.
+    fn run_final_passes(&mut self, codegen_config: &CodegenConfig) {
+        if codegen_config.deterministic {
+            let codegen_config = CodegenConfig {
+                deterministic: false,
+                ..codegen_config.clone()
+            };
+            self.run_optimization_passes(
+                ["reorder-functions-by-name", "reorder-globals"],
+                &codegen_config,
+            )
+            .expect("passes are registered");
+        }
+    }
+
+    /// Run a specified set of optimization passes on the module and validate the result.
+    ///
+    /// Nothing is rolled back if validation fails: the module is left in the state the passes
+    /// produced.
+    pub fn run_passes_validated<B: AsRef<str>, I: IntoIterator<Item = B>>(
+        &mut self,
+        passes: I,
+        codegen_config: &CodegenConfig,
+    ) -> Result<(), TransformError> {
+        let passes: Vec<B> = passes.into_iter().collect();
+        if let Some(pass) = passes.iter().find(|pass| !is_valid_pass(pass.as_ref())) {
+            return Err(InvalidPassError::new(pass.as_ref()).into());
+        }
+        self.run_optimization_passes(&passes, codegen_config)
+            .expect("pass names are checked above");
+        self.validate()?;
+        Ok(())
+    }
+
+    /// Validate a module, printing errors to stdout on problems.
+    ///
+    /// This module is private since you can't create an invalid module through the
+    /// safe public API.
+    #[cfg(test)]
+    fn is_valid(&self) -> bool {
+        unsafe { binaryen_sys::BinaryenModuleSafeValidate(self.inner.raw) == 1 }
+    }
+
+    /// Validate a module, returning the validator diagnostics on problems.
+    ///
+    /// All features are enabled during validation, so e.g. SIMD instructions are accepted
+    /// regardless of the features the module was read with.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.validate_with_features(Features::all())
+    }
+
+    /// Validate a module with only the given features enabled, returning the validator
+    /// diagnostics on problems.
+    ///
+    /// E.g. `validate_with_features(Features::MVP)` checks whether the module can run on an
+    /// MVP-only engine. The features set on the module are left as they are.
+    pub fn validate_with_features(&self, features: Features) -> Result<(), ValidationError> {
+        let message = unsafe {
+            take_shim_string(
+                binaryen_sys::BinaryenModuleSafeValidateWithFeaturesAndMessage(
+                    self.inner.raw,
+                    features.to_raw(),
+                ),
+            )
+        };
+        match message {
+            None => Ok(()),
+            Some(message) => Err(ValidationError { message }),
+        }
+    }
+
+    /// Panic with the validator diagnostics if the module is invalid.
+    ///
+    /// Meant for tests, where `module.assert_valid()` gives a much better failure message
+    /// than `assert!(module.is_valid())`.
+    pub fn assert_valid(&self) {
+        if let Err(err) = self.validate() {
+            panic!("{}", err);
+        }
+    }
+
+    /// Set the name of the function with the given index.
+    ///
+    /// All references to the function are updated. The name is emitted into the names section
+    /// when the module is written with `debug_info` set, see [`Module::write_with_config`].
+    ///
+    /// Returns `Err` if there is no function with such index, if the name contains a NUL byte
+    /// or if the name is already taken by another function.
+    pub fn set_function_name(&mut self, index: u32, name: &str) -> Result<(), ()> {
+        if index >= unsafe { binaryen_sys::BinaryenGetNumFunctions(self.inner.raw) } {
+            return Err(());
+        }
+        let name = CString::new(name).map_err(|_| ())?;
+        let renamed = unsafe {
+            binaryen_sys::BinaryenModuleSetFunctionName(self.inner.raw, index, name.as_ptr())
+        };
+        if renamed == 0 {
+            return Err(());
+        }
+        Ok(())
+    }
+
+    /// Copy everything `other` defines into this module.
+    ///
+    /// Functions, globals, tags, memories, tables, data and element segments and exports are
+    /// copied over, and the features of `other` are enabled on this module. The start function of
+    /// `other` is not copied. This is not a linker: imports are left as is, even if they could be
+    /// resolved by the exports of the other module.
+    ///
+    /// Returns `Err` without changing anything if a name from `other` is already taken in this
+    /// module. Note that Binaryen names items without a name in the names section after their
+    /// index, so such modules are likely to clash.
+    pub fn merge(&mut self, other: &Module) -> Result<(), MergeError> {
+        let mut export_clash = 0;
+        let clash = unsafe {
+            take_shim_string(binaryen_sys::BinaryenModuleMerge(
+                self.inner.raw,
+                other.inner.raw,
+                &mut export_clash,
+            ))
+        };
+        match clash {
+            None => Ok(()),
+            Some(name) if export_clash != 0 => Err(MergeError::DuplicateExport(name)),
+            Some(name) => Err(MergeError::DuplicateName(name)),
+        }
+    }
+
+    /// Check whether the module defines or imports a memory.
+    pub fn has_memory(&self) -> bool {
+        unsafe { binaryen_sys::BinaryenHasMemory(self.inner.raw) }
+    }
+
+    /// Check whether the module defines or imports a table.
+    pub fn has_table(&self) -> bool {
+        unsafe { binaryen_sys::BinaryenGetNumTables(self.inner.raw) > 0 }
+    }
+
+    /// Get the heap types of the module in type index order.
+    fn heap_types(&self) -> Vec<binaryen_sys::BinaryenHeapType> {
+        let raw = self.inner.raw;
+        unsafe {
+            let mut heap_types = vec![0; binaryen_sys::BinaryenShimGetNumHeapTypes(raw) as usize];
+            binaryen_sys::BinaryenShimGetHeapTypes(raw, heap_types.as_mut_ptr());
+            heap_types
+        }
+    }
+
+    /// Get the names of the module's heap types that have one, in type index order.
+    ///
+    /// Type names come from the name section, [`Module::set_type_name`] or the `name-types`
+    /// pass, see [`Module::name_types`].
+    pub fn type_names(&self) -> Vec<String> {
+        let raw = self.inner.raw;
+        self.heap_types()
+            .into_iter()
+            .filter_map(|heap_type| unsafe {
+                let name = binaryen_sys::BinaryenShimGetHeapTypeName(raw, heap_type);
+                if name.is_null() {
+                    None
+                } else {
+                    Some(name_from_raw(name).to_string())
+                }
+            })
+            .collect()
+    }
+
+    /// Set the name of the heap type with the given type index.
+    ///
+    /// Type indices are assigned the way the binary writer does, so they can change when the
+    /// module is transformed. Returns `Err` if there is no type with the given index.
+    pub fn set_type_name(&mut self, index: u32, name: &str) -> Result<(), ()> {
+        let heap_type = *self.heap_types().get(index as usize).ok_or(())?;
+        let name = CString::new(name).map_err(|_| ())?;
+        unsafe {
+            binaryen_sys::BinaryenModuleSetTypeName(self.inner.raw, heap_type, name.as_ptr())
+        };
+        Ok(())
+    }
+
+    /// Get the features enabled on this module.
+    pub fn features(&self) -> Features {
+        Features::from_raw(unsafe { binaryen_sys::BinaryenModuleGetFeatures(self.inner.raw) })
+    }
+
+    /// Set the features enabled on this module.
+    ///
+    /// Passes consult the enabled features, e.g. to decide whether they are allowed to
+    /// introduce instructions from a proposal.
+    pub fn set_features(&mut self, features: Features) {
+        unsafe { binaryen_sys::BinaryenModuleSetFeatures(self.inner.raw, features.to_raw()) }
+    }
+
+    /// Get the names in the `target_features` custom section the module is written with, as
+    /// parsed from the output of [`Module::write`]. Returns `None` if no such section is written.
+    ///
+    /// Binaryen doesn't keep the section it read: it applies the section to the module's
+    /// features and writes one back listing the enabled features. Disallowed features and names
+    /// Binaryen doesn't know are therefore dropped, and features enabled later with
+    /// [`Module::set_features`] are listed. Use [`Module::read_target_features`] to get the
+    /// entries of a binary as written.
+    pub fn target_features(&self) -> Option<Vec<String>> {
+        if unsafe { binaryen_sys::BinaryenShimHasFeaturesSection(self.inner.raw) } == 0 {
+            return None;
+        }
+        let features = Module::read_target_features(&self.write())
+            .expect("Binaryen wrote a malformed target_features section")?;
+        Some(features.into_iter().map(|feature| feature.name).collect())
+    }
+
+    /// Replace the module's `producers` custom section.
+    ///
+    /// Each entry is a `(field, name, version)` triple, where the field is one of
+    /// `"language"`, `"processed-by"` and `"sdk"`, e.g. `("processed-by", "mytool", "1.0")`.
+    /// Entries of the same field are grouped in the order given. Any existing `producers`
+    /// section is removed, and no new one is added if `entries` is empty.
+    pub fn set_producers(&mut self, entries: &[(&str, &str, &str)]) {
+        self.run_pass_with_arguments("strip-producers", &[]);
+        if entries.is_empty() {
+            return;
+        }
+
+        let mut fields: Vec<(&str, Vec<(&str, &str)>)> = vec![];
+        for &(field, name, version) in entries {
+            match fields.iter_mut().find(|(existing, _)| *existing == field) {
+                Some((_, values)) => values.push((name, version)),
+                None => fields.push((field, vec![(name, version)])),
+            }
+        }
+
+        let mut payload = vec![];
+        encoding::write_leb128_u32(&mut payload, fields.len() as u32);
+        for (field, values) in fields {
+            encoding::write_name(&mut payload, field.as_bytes());
+            encoding::write_leb128_u32(&mut payload, values.len() as u32);
+            for (name, version) in values {
+                encoding::write_name(&mut payload, name.as_bytes());
+                encoding::write_name(&mut payload, version.as_bytes());
+            }
+        }
+
+        let section_name = CString::new("producers").unwrap();
+        unsafe {
+            binaryen_sys::BinaryenAddCustomSection(
+                self.inner.raw,
+                section_name.as_ptr(),
+                payload.as_ptr() as *const c_char,
+                payload.len() as u32,
+            )
+        }
+    }
+
+    /// Check whether the module uses the given feature.
+    ///
+    /// The detection is done by the validator: the module uses a feature if it's valid with all
+    /// features enabled, but fails validation once `feature` is disabled. Note that this says
+    /// nothing about the features the module declares, such as the ones listed in the
+    /// `target_features` section.
+    pub fn uses_feature(&self, feature: Feature) -> bool {
+        unsafe {
+            let all = binaryen_sys::BinaryenFeatureAll();
+            let valid_with = |features| {
+                binaryen_sys::BinaryenModuleSafeValidateWithFeatures(self.inner.raw, features) == 1
+            };
+            valid_with(all) && !valid_with(all & !feature.to_raw())
+        }
+    }
+
+    /// Serialize a module into the text format.
+    ///
+    /// Unless the module has only the MVP features enabled, the text starts with a comment
+    /// listing them, e.g. `;; features: simd,bulk-memory`. [`Module::parse_text`] enables the
+    /// listed features again, and the names are the ones of `wasm-opt`'s `--enable-*` flags.
+    pub fn write_text(&self) -> String {
+        let text = unsafe {
+            take_shim_string(binaryen_sys::BinaryenModuleAllocateAndWriteText(
+                self.inner.raw,
+            ))
+            .unwrap_or_default()
+        };
+        let features = self.features();
+        if features == Features::MVP {
+            return text;
+        }
+        format!("{}{}\n{}", FEATURES_COMMENT, features, text)
+    }
+
+    /// Serialize a module into binary form.
+    pub fn write(&self) -> Vec<u8> {
+        self.write_with_config(&CodegenConfig::default())
+    }
+
+    /// Serialize a module into binary form, emitting the names section if
+    /// `codegen_config.debug_info` is set.
+    pub fn write_with_config(&self, codegen_config: &CodegenConfig) -> Vec<u8> {
+        self.write_raw(codegen_config.debug_info, None).0
+    }
+
+    /// Serialize a module into binary form along with a source map.
+    ///
+    /// With [`SourceMapMode::External`] the source map is returned next to the binary, which
+    /// refers to it by the given URL. With [`SourceMapMode::Inline`] the source map is embedded
+    /// into the `sourceMappingURL` section as a base64 `data:` URL, and `None` is returned for it.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the URL contains a NUL byte.
+    pub fn write_with_source_map_mode(
+        &self,
+        mode: &SourceMapMode,
+    ) -> io::Result<(Vec<u8>, Option<String>)> {
+        Ok(match mode {
+            SourceMapMode::None => (self.write(), None),
+            SourceMapMode::External(url) => self.write_raw(false, Some(&source_map_url(url)?)),
+            SourceMapMode::Inline => {
+                // The URL is written after the code, so getting the source map with a
+                // placeholder URL doesn't affect the offsets it refers to.
+                let placeholder = CString::new("inline").unwrap();
+                let source_map = self
+                    .write_raw(false, Some(&placeholder))
+                    .1
+                    .unwrap_or_default();
+                let url = format!(
+                    "data:application/json;base64,{}",
+                    encoding::base64_encode(source_map.as_bytes())
+                );
+
+                let mut binary = self.write();
+                let mut payload = vec![];
+                encoding::write_name(&mut payload, url.as_bytes());
+                encoding::append_custom_section(&mut binary, "sourceMappingURL", &payload);
+                (binary, None)
+            }
+        })
+    }
+
+    /// Serialize a module into binary form with the given options.
+    ///
+    /// Returns the binary along with the source map if `options.source_map_url` is set. Fails
+    /// if the symbol map can't be written, or with [`io::ErrorKind::InvalidInput`] if the source
+    /// map URL contains a NUL byte, in which case no symbol map is written either.
+    pub fn write_with_options(
+        &self,
+        options: &WriteOptions,
+    ) -> io::Result<(Vec<u8>, Option<String>)> {
+        let url = options
+            .source_map_url
+            .as_deref()
+            .map(source_map_url)
+            .transpose()?;
+        if let Some(path) = &options.symbol_map {
+            fs::write(path, self.symbol_map())?;
+        }
+        Ok(self.write_raw(options.debug_info, url.as_deref()))
+    }
+
+    fn write_raw(
+        &self,
+        debug_info: bool,
+        source_map_url: Option<&CStr>,
+    ) -> (Vec<u8>, Option<String>) {
+        unsafe {
+            let mut error = ptr::null_mut();
+            let write_result = binaryen_sys::BinaryenModuleAllocateAndWriteWithSettings(
+                self.inner.raw,
+                source_map_url.map_or(ptr::null(), |url| url.as_ptr()),
+                debug_info as i32,
+                &mut error,
+            );
+            if let Some(message) = take_shim_string(error) {
+                panic!("Binaryen hit a fatal error: {}", message.trim_end());
+            }
+
+            // Create a slice from the resulting array and then copy it in vector.
+            let binary_buf = if write_result.binaryBytes == 0 {
+                vec![]
+            } else {
+                slice::from_raw_parts(write_result.binary as *const u8, write_result.binaryBytes)
+                    .to_vec()
+            };
+            let source_map = if write_result.sourceMap.is_null() {
+                None
+            } else {
+                Some(
+                    CStr::from_ptr(write_result.sourceMap)
+                        .to_string_lossy()
+                        .into_owned(),
+                )
+            };
+
+            // This will free buffers in the write_result.
+            binaryen_sys::BinaryenShimDisposeBinaryenModuleAllocateAndWriteResult(write_result);
+
+            (binary_buf, source_map)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! wat2wasm {
+        ($x:expr) => {
+            wat::parse_str($x).unwrap()
+        };
+    }
+
+    #[test]
+    fn module_reading() {
+        // The current version of wasm is 1, thus module with the version 0 is invalid.
+        let invalid_module = b"\0asm\0\0\0\0";
+        let valid_module = b"\0asm\x01\0\0\0";
+
+        assert_eq!(
+            Module::read(invalid_module).err(),
+            Some(ReadError::BadMagic)
+        );
+        assert!(Module::read(valid_module).is_ok());
+
+        assert_eq!(Module::read(b"").err(), Some(ReadError::TooShort));
+        assert_eq!(Module::read(b"\0as").err(), Some(ReadError::TooShort));
+        assert_eq!(
+            Module::read(b"\0asm\x01\0").err(),
+            Some(ReadError::TooShort)
+        );
+        assert_eq!(Module::read(b"\0wasm").err(), Some(ReadError::BadMagic));
+        assert_eq!(
+            Module::read(b"\0ASM\x01\0\0\0").err(),
+            Some(ReadError::BadMagic)
+        );
+
+        // A section id followed by a section size that runs past the end of the input.
+        let truncated_section = b"\0asm\x01\0\0\0\x01\x7f";
+        assert_eq!(
+            Module::read(truncated_section).err(),
+            Some(ReadError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_optimization_passes() {
+        const CODE: &'static str = r#"
+            (module
+                (table 1 1 funcref)
+
+                (type $return_i32 (func (result i32)))
+                (func $test (; 0 ;) (result i32)
+                    (call_indirect (type $return_i32)
+                        (unreachable)
+                    )
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+
+        assert!(module.is_valid());
+
+        module
+            .run_optimization_passes(&["vacuum", "untee"], &CodegenConfig::default())
+            .expect("passes succeeded");
+
+        assert!(module.is_valid());
+    }
+
+    #[test]
+    fn test_validate_simd() {
+        const CODE: &'static str = r#"
+            (module
+                (func $splat (param i32) (result v128)
+                    (i32x4.splat (local.get 0))
+                )
+            )
+        "#;
+        let module = Module::read(&wat2wasm!(CODE)).unwrap();
+        assert_eq!(module.validate(), Ok(()));
+        module.assert_valid();
+    }
+
+    #[test]
+    fn test_validate_with_features() {
+        const CODE: &'static str = r#"
+            (module
+                (func $splat (param i32) (result v128)
+                    (i32x4.splat (local.get 0))
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        module.set_features(Features::SIMD);
+        assert_eq!(module.validate_with_features(Features::SIMD), Ok(()));
+
+        let err = module.validate_with_features(Features::MVP).unwrap_err();
+        assert!(err.message().contains("SIMD"), "{}", err);
+        assert_eq!(module.features(), Features::SIMD);
+    }
+
+    #[test]
+    #[should_panic(expected = "module is invalid")]
+    fn test_assert_valid_panics() {
+        // A function returning an i64 constant while declaring an i32 result.
+        let module = Module::new();
+        unsafe {
+            let body = binaryen_sys::BinaryenConst(
+                module.inner.raw,
+                binaryen_sys::BinaryenLiteralInt64(0),
+            );
+            let name = CString::new("test").unwrap();
+            binaryen_sys::BinaryenAddFunction(
+                module.inner.raw,
+                name.as_ptr(),
+                binaryen_sys::BinaryenTypeNone(),
+                binaryen_sys::BinaryenTypeInt32(),
+                ptr::null_mut(),
+                0,
+                body,
+            );
+        }
+        module.assert_valid();
+    }
+
+    #[test]
+    fn test_set_function_name() {
+        const CODE: &'static str = r#"
+            (module
+                (func (export "main") (call 1))
+                (func)
+            )
+        "#;
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        module.set_function_name(0, "main_fn").unwrap();
+        module.set_function_name(1, "callee").unwrap();
+        assert!(module.set_function_name(1, "main_fn").is_err());
+        assert!(module.set_function_name(2, "missing").is_err());
+        module.assert_valid();
+
+        let without_names = module.write();
+        let with_names = module.write_with_config(&CodegenConfig {
+            debug_info: true,
+            ..CodegenConfig::default()
+        });
+        let contains = |haystack: &[u8], needle: &[u8]| {
+            haystack.windows(needle.len()).any(|window| window == needle)
+        };
+        assert!(!contains(&without_names, b"callee"));
+        assert!(contains(&with_names, b"callee"));
+        assert!(contains(&with_names, b"main_fn"));
+    }
+
+    #[test]
+    fn test_owned_module_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<OwnedModule>();
+
+        let input = wat2wasm!(r#"(module (func (export "main") (nop)))"#);
+        let owned = Module::read(&input).unwrap().into_owned().ok().unwrap();
+        let written = std::thread::spawn(move || {
+            let mut module = owned.into_module();
+            module.optimize(&CodegenConfig::default());
+            module.assert_valid();
+            module.write()
+        })
+        .join()
+        .unwrap();
+        assert!(Module::read(&written).is_ok());
+    }
+
+    #[test]
+    fn test_deep_clone() {
+        const CODE: &'static str = r#"
+            (module
+                (func $a (export "a") (result i32) (i32.add (i32.const 1) (i32.const 2)))
+            )
+        "#;
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        let copy = module.deep_clone();
+        assert_eq!(copy.write(), module.write());
+
+        module.optimize(&CodegenConfig::default());
+        assert_ne!(copy.write(), module.write());
+        drop(module);
+        copy.assert_valid();
+    }
+
+    #[test]
+    fn test_owned_deep_clone() {
+        let module = Module::read(&wat2wasm!(r#"(module (func (export "main") (nop)))"#)).unwrap();
+        let owned = module.into_owned().ok().unwrap();
+        let copy = owned.deep_clone();
+
+        let module = owned.into_module();
+        let name = CString::new("main").unwrap();
+        unsafe { binaryen_sys::BinaryenRemoveExport(module.inner.raw, name.as_ptr()) };
+        assert_eq!(module.exports().count(), 0);
+        assert_eq!(copy.into_module().exports().count(), 1);
+    }
+
+    #[test]
+    fn test_type_names() {
+        const CODE: &'static str = r#"
+            (module
+                (type (struct (field i32)))
+                (type (array i8))
+                (func (param (ref null 0) (ref null 1)))
+            )
+        "#;
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        assert!(module.type_names().is_empty());
+        assert!(module.set_type_name(0, "point").is_ok());
+        assert_eq!(module.type_names(), vec!["point".to_string()]);
+
+        assert!(module.set_type_name(42, "missing").is_err());
+        assert!(module.write_text().contains("$point"));
+    }
+
+    #[test]
+    fn test_target_features() {
+        let mut binary = wat2wasm!("(module)");
+        assert_eq!(Module::read(&binary).unwrap().target_features(), None);
+
+        let mut payload = vec![];
+        encoding::write_leb128_u32(&mut payload, 3);
+        for (prefix, name) in [(b'+', "simd128"), (b'+', "bulk-memory"), (b'-', "atomics")] {
+            payload.push(prefix);
+            encoding::write_name(&mut payload, name.as_bytes());
+        }
+        encoding::append_custom_section(&mut binary, "target_features", &payload);
+
+        let module = Module::read(&binary).unwrap();
+        assert_eq!(
+            module.target_features(),
+            Some(vec!["bulk-memory".to_string(), "simd128".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_set_producers() {
+        let count = |haystack: &[u8], needle: &[u8]| {
+            haystack
+                .windows(needle.len())
+                .filter(|window| *window == needle)
+                .count()
+        };
+
+        let mut module = Module::read(&wat2wasm!("(module)")).unwrap();
+        module.set_producers(&[
+            ("language", "Rust", "1.75"),
+            ("processed-by", "rustc", "1.75"),
+            ("processed-by", "mytool", "0.1"),
+        ]);
+        let binary = module.write();
+        assert_eq!(count(&binary, b"producers"), 1);
+        assert_eq!(count(&binary, b"processed-by"), 1);
+        assert_eq!(count(&binary, b"mytool"), 1);
+
+        let mut module = Module::read(&binary).unwrap();
+        module.set_producers(&[("processed-by", "othertool", "2.0")]);
+        let binary = module.write();
+        assert_eq!(count(&binary, b"producers"), 1);
+        assert_eq!(count(&binary, b"othertool"), 1);
+        assert_eq!(count(&binary, b"mytool"), 0);
+
+        module.set_producers(&[]);
+        assert_eq!(count(&module.write(), b"producers"), 0);
+    }
+
+    #[test]
+    fn test_uses_feature() {
+        const CODE: &'static str = r#"
+            (module
+                (memory 1 1 shared)
+                (func $load (param i32) (result i32)
+                    (i32.atomic.load (local.get 0))
+                )
+                (func $splat (param i32) (result v128)
+                    (i32x4.splat (local.get 0))
+                )
+            )
+        "#;
+        let module = Module::read(&wat2wasm!(CODE)).unwrap();
+        assert!(module.uses_feature(Feature::Atomics));
+        assert!(module.uses_feature(Feature::Simd));
+        assert!(!module.uses_feature(Feature::ExceptionHandling));
+
+        let mvp = Module::read(&wat2wasm!(r#"(module (func (nop)))"#)).unwrap();
+        for feature in Feature::ALL {
+            assert!(!mvp.uses_feature(*feature), "{:?} detected", feature);
         }
     }
 
-    /// Run the standard optimization passes on the module.
-    pub fn optimize(&mut self, codegen_config: &CodegenConfig) {
-        unsafe {
-            binaryen_sys::BinaryenModuleRunPassesWithSettings(
-                self.inner.raw,
-                std::ptr::null_mut(),
-                0 as u32,
-                codegen_config.shrink_level as i32,
-                codegen_config.optimization_level as i32,
-                codegen_config.debug_info as i32,
+    #[test]
+    fn test_has_memory_and_table() {
+        let empty = Module::read(&wat2wasm!("(module)")).unwrap();
+        assert!(!empty.has_memory());
+        assert!(!empty.has_table());
+
+        let defined = Module::read(&wat2wasm!("(module (memory 1) (table 1 funcref))")).unwrap();
+        assert!(defined.has_memory());
+        assert!(defined.has_table());
+
+        const IMPORTED: &'static str = r#"
+            (module
+                (import "env" "memory" (memory 1))
+                (import "env" "table" (table 1 funcref))
             )
-        }
+        "#;
+        let imported = Module::read(&wat2wasm!(IMPORTED)).unwrap();
+        assert!(imported.has_memory());
+        assert!(imported.has_table());
     }
 
-    /// Run a specified set of optimization passes on the module.
-    pub fn run_optimization_passes<B: AsRef<str>, I: IntoIterator<Item = B>>(
-        &mut self,
-        passes: I,
-        codegen_config: &CodegenConfig,
-    ) -> Result<(), ()> {
-        let mut cstr_vec: Vec<_> = vec![];
-
-        for pass in passes {
-            if !is_valid_pass(pass.as_ref()) {
-                return Err(());
-            }
+    #[test]
+    fn test_merge() {
+        let mut module = Module::read(&wat2wasm!(
+            r#"(module (func $a (export "a") (result i32) (i32.const 1)))"#
+        ))
+        .unwrap();
+        let other = Module::read(&wat2wasm!(
+            r#"(module
+                (global $g (mut i32) (i32.const 0))
+                (func $b (export "b") (result i32) (call $c))
+                (func $c (result i32) (global.get $g))
+            )"#
+        ))
+        .unwrap();
 
-            cstr_vec.push(CString::new(pass.as_ref()).unwrap());
+        module.merge(&other).unwrap();
+        module.assert_valid();
+        unsafe {
+            assert_eq!(binaryen_sys::BinaryenGetNumFunctions(module.inner.raw), 3);
+            assert_eq!(binaryen_sys::BinaryenGetNumExports(module.inner.raw), 2);
         }
 
-        // NOTE: BinaryenModuleRunPasses expectes a mutable ptr
-        let mut ptr_vec: Vec<_> = cstr_vec.iter().map(|pass| pass.as_ptr()).collect();
+        // Merging again clashes on the function names.
+        assert!(matches!(
+            module.merge(&other),
+            Err(MergeError::DuplicateName(_))
+        ));
 
+        let same_export =
+            Module::read(&wat2wasm!(r#"(module (func $other_a (export "a")))"#)).unwrap();
+        assert_eq!(
+            module.merge(&same_export),
+            Err(MergeError::DuplicateExport("a".to_string()))
+        );
         unsafe {
-            binaryen_sys::BinaryenModuleRunPassesWithSettings(
-                self.inner.raw,
-                ptr_vec.as_mut_ptr(),
-                ptr_vec.len() as u32,
-                codegen_config.shrink_level as i32,
-                codegen_config.optimization_level as i32,
-                codegen_config.debug_info as i32,
-            )
-        };
-        Ok(())
+            assert_eq!(binaryen_sys::BinaryenGetNumFunctions(module.inner.raw), 3);
+        }
     }
 
-    /// Validate a module, printing errors to stdout on problems.
-    ///
-    /// This module is private since you can't create an invalid module through the
-    /// safe public API.
-    #[cfg(test)]
-    fn is_valid(&self) -> bool {
-        unsafe { binaryen_sys::BinaryenModuleSafeValidate(self.inner.raw) == 1 }
+    #[test]
+    fn test_features() {
+        let mut module = Module::new();
+        module.set_features(Features::SIMD | Features::SIGN_EXT);
+        assert_eq!(module.features(), Features::SIMD | Features::SIGN_EXT);
+        module.set_features(Features::MVP);
+        assert_eq!(module.features(), Features::MVP);
     }
 
-    /// Serialize a module into binary form.
-    pub fn write(&self) -> Vec<u8> {
-        unsafe {
-            let write_result =
-                binaryen_sys::BinaryenModuleAllocateAndWrite(self.inner.raw, ptr::null());
+    #[test]
+    fn test_optimize_with_features() {
+        const CODE: &'static str = r#"
+            (module
+                (func (export "sext") (param i32) (result i32)
+                    (i32.shr_s (i32.shl (local.get 0) (i32.const 24)) (i32.const 24))
+                )
+            )
+        "#;
+        let config = CodegenConfig {
+            optimization_level: 2,
+            ..CodegenConfig::default()
+        };
 
-            // Create a slice from the resulting array and then copy it in vector.
-            let binary_buf = if write_result.binaryBytes == 0 {
-                vec![]
-            } else {
-                slice::from_raw_parts(write_result.binary as *const u8, write_result.binaryBytes)
-                    .to_vec()
-            };
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        module.optimize_with_features(&config, Features::MVP);
+        assert_eq!(module.features(), Features::MVP);
+        assert!(!module.write_text().contains("i32.extend8_s"));
 
-            // This will free buffers in the write_result.
-            binaryen_sys::BinaryenShimDisposeBinaryenModuleAllocateAndWriteResult(write_result);
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        module.optimize_with_features(&config, Features::SIGN_EXT);
+        assert_eq!(module.features(), Features::SIGN_EXT);
+        assert!(module.write_text().contains("i32.extend8_s"));
 
-            binary_buf
-        }
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        module.optimize_mvp(&config);
+        assert_eq!(module.features(), Features::MVP);
+        assert!(!module.write_text().contains("i32.extend8_s"));
+
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        module.optimize_all_features(&config);
+        assert_eq!(module.features(), Features::all());
+        assert!(module.write_text().contains("i32.extend8_s"));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_zero_filled_memory() {
+        const CODE: &'static str = r#"
+            (module
+                (import "env" "memory" (memory 1))
+                (data (i32.const 0) "\00\00\00\00\00\00\00\00")
+            )
+        "#;
+        let num_data_segments = |module: &Module| unsafe {
+            binaryen_sys::BinaryenGetNumMemorySegments(module.inner.raw)
+        };
 
-    macro_rules! wat2wasm {
-        ($x:expr) => {
-            wat::parse_str($x).unwrap()
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        module
+            .run_optimization_passes(["memory-packing"], &CodegenConfig::default())
+            .unwrap();
+        assert_eq!(num_data_segments(&module), 1);
+
+        let config = CodegenConfig {
+            zero_filled_memory: true,
+            ..CodegenConfig::default()
         };
+        module
+            .run_optimization_passes(["memory-packing"], &config)
+            .unwrap();
+        assert_eq!(num_data_segments(&module), 0);
+        assert!(!unsafe { binaryen_sys::BinaryenGetZeroFilledMemory() });
     }
 
     #[test]
-    fn module_reading() {
-        // The current version of wasm is 1, thus module with the version 0 is invalid.
-        let invalid_module = b"\0asm\0\0\0\0";
-        let valid_module = b"\0asm\x01\0\0\0";
+    fn test_deterministic() {
+        const CODE: &'static str = r#"
+            (module
+                (func $b (export "b") (result i32) (call $a))
+                (func $a (export "a") (result i32) (i32.const 1))
+            )
+        "#;
+        let config = CodegenConfig {
+            deterministic: true,
+            ..CodegenConfig::default()
+        };
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        module.optimize(&config);
+        module.assert_valid();
+        let text = module.write_text();
+        assert!(text.find("(func $a").unwrap() < text.find("(func $b").unwrap());
 
-        assert!(Module::read(invalid_module).is_err());
-        assert!(Module::read(valid_module).is_ok());
+        let mut other = Module::read(&wat2wasm!(CODE)).unwrap();
+        other.optimize(&config);
+        assert_eq!(module.write(), other.write());
     }
 
     #[test]
-    fn test_optimization_passes() {
+    fn test_inlining_thresholds() {
         const CODE: &'static str = r#"
             (module
-                (table 1 1 funcref)
-
-                (type $return_i32 (func (result i32)))
-                (func $test (; 0 ;) (result i32)
-                    (call_indirect (type $return_i32)
-                        (unreachable)
-                    )
+                (func $f (param i32) (result i32)
+                    (i32.mul (i32.add (local.get 0) (i32.const 1)) (i32.const 3))
+                )
+                (func (export "main") (param i32) (result i32)
+                    (i32.add (call $f (local.get 0)) (call $f (i32.const 2)))
                 )
             )
         "#;
         let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        module
+            .run_optimization_passes(["inlining"], &CodegenConfig::default())
+            .unwrap();
+        assert!(module.write_text().contains("(call $f"));
 
-        assert!(module.is_valid());
-
+        let config = CodegenConfig {
+            always_inline_max_size: Some(100),
+            ..CodegenConfig::default()
+        };
         module
-            .run_optimization_passes(&["vacuum", "untee"], &CodegenConfig::default())
-            .expect("passes succeeded");
+            .run_optimization_passes(["inlining"], &config)
+            .unwrap();
+        module.assert_valid();
+        assert!(!module.write_text().contains("(call $f"));
+    }
 
-        assert!(module.is_valid());
+    #[test]
+    fn test_fatal_error_panics() {
+        let mut module = Module::read(&wat2wasm!("(module)")).unwrap();
+        // RemoveUnusedTypes refuses to run without a closed world.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            module
+                .run_optimization_passes(["remove-unused-types"], &CodegenConfig::default())
+                .unwrap();
+        }));
+        let message = result.unwrap_err();
+        let message = message.downcast_ref::<String>().unwrap();
+        assert!(message.contains("closed-world"), "{}", message);
+
+        // The module can still be used afterwards.
+        module.optimize(&CodegenConfig::default());
+        module.assert_valid();
     }
 
     #[test]
@@ -200,6 +1882,35 @@ mod tests {
             .is_err());
     }
 
+    #[test]
+    fn test_run_passes_validated() {
+        let mut module =
+            Module::read(&wat2wasm!(r#"(module (func (export "main") (nop)))"#)).unwrap();
+        module
+            .run_passes_validated(["vacuum", "untee"], &CodegenConfig::default())
+            .unwrap();
+
+        match module.run_passes_validated(["vacuum", "invalid"], &CodegenConfig::default()) {
+            Err(TransformError::InvalidPass(err)) => assert_eq!(err.pass(), "invalid"),
+            _ => panic!("expected an invalid pass error"),
+        }
+    }
+
+    #[test]
+    fn test_opt_level() {
+        let config = OptLevel::Oz.to_codegen_config();
+        assert_eq!(config.shrink_level, CodegenConfig::MAX_SHRINK_LEVEL);
+        assert_eq!(config.optimization_level, 2);
+        assert!(!config.debug_info);
+
+        let config = OptLevel::O4.to_codegen_config();
+        assert_eq!(config.shrink_level, 0);
+        assert_eq!(
+            config.optimization_level,
+            CodegenConfig::MAX_OPTIMIZATION_LEVEL
+        );
+    }
+
     #[test]
     fn optimization_pass_list() {
         let pass_list = [
@@ -327,6 +2038,425 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_available_passes() {
+        let passes = available_passes();
+        let vacuum = passes.iter().find(|pass| pass.name == "vacuum").unwrap();
+        assert!(!vacuum.description.is_empty());
+
+        // Everything the build script extracted is known to the registry as well.
+        for name in binaryen_sys::passes::PASS_NAMES {
+            assert!(
+                passes.iter().any(|pass| pass.name == *name),
+                "missing pass: {}",
+                name
+            );
+        }
+        for pass in &passes {
+            assert!(is_valid_pass(&pass.name), "not a valid pass: {}", pass.name);
+        }
+
+        // The passes only registered for Binaryen's own tests are rejected.
+        unsafe {
+            for index in 0..binaryen_sys::BinaryenShimGetNumRegisteredPasses() {
+                let name = binaryen_sys::BinaryenShimGetRegisteredPassName(index);
+                let name = take_shim_string(name).unwrap();
+                let c_name = CString::new(name.as_str()).unwrap();
+                if binaryen_sys::BinaryenShimIsPassHidden(c_name.as_ptr()) == 1 {
+                    assert!(!is_valid_pass(&name), "hidden pass is valid: {}", name);
+                    assert!(!passes.iter().any(|pass| pass.name == name));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_passes_with_prefix() {
+        let passes: Vec<_> = passes_with_prefix("optimize-").collect();
+        assert!(passes.contains(&"optimize-instructions"));
+        assert!(passes.contains(&"optimize-stack-ir"));
+        for pass in passes {
+            assert!(pass.starts_with("optimize-"));
+            assert!(is_valid_pass(pass), "not a valid pass: {}", pass);
+        }
+
+        assert_eq!(passes_with_prefix("no-such-pass-").count(), 0);
+    }
+
+    #[test]
+    fn test_optimize_and_report() {
+        let input = wat2wasm!(
+            r#"(module (func (export "main") (result i32) (i32.add (i32.const 1) (i32.const 2))))"#
+        );
+        let mut module = Module::read(&input).unwrap();
+        let config = CodegenConfig {
+            optimization_level: 2,
+            ..CodegenConfig::default()
+        };
+        assert!(module.optimize_and_report(&config));
+        assert!(!module.optimize_and_report(&config));
+    }
+
+    #[test]
+    fn test_read_validated() {
+        let valid = wat2wasm!(r#"(module (func (export "main") (nop)))"#);
+        assert!(Module::read_validated(&valid).is_ok());
+
+        assert_eq!(
+            Module::read_validated(b"\0asm").err(),
+            Some(ReadValidatedError::Read(ReadError::TooShort))
+        );
+
+        // Well-formed, but the argument has the wrong type. `wat` doesn't validate.
+        let invalid = wat2wasm!(r#"(module (func $f (param i32)) (func (call $f (i64.const 0))))"#);
+        assert!(Module::read(&invalid).is_ok());
+        match Module::read_validated(&invalid).err() {
+            Some(ReadValidatedError::Invalid(err)) => assert!(!err.message().is_empty()),
+            err => panic!("unexpected result: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_semantically_eq() {
+        let module = Module::read(&wat2wasm!(
+            r#"(module (func $add (export "add") (param i32 i32) (result i32)
+                (i32.add (local.get 0) (local.get 1))))"#
+        ))
+        .unwrap();
+        let renamed = Module::parse_text(
+            r#"(module (func $sum (export "add") (param $a i32) (param $b i32) (result i32)
+                (i32.add (local.get $a) (local.get $b))))"#,
+        )
+        .unwrap();
+        assert_ne!(module.write_text(), renamed.write_text());
+        assert!(module.semantically_eq(&renamed));
+        assert!(module.semantically_eq(&module.deep_clone()));
+
+        let swapped = Module::parse_text(
+            r#"(module (func $add (export "add") (param i32 i32) (result i32)
+                (i32.add (local.get 1) (local.get 0))))"#,
+        )
+        .unwrap();
+        assert!(!module.semantically_eq(&swapped));
+
+        let mut with_features = module.deep_clone();
+        with_features.set_features(Features::SIMD);
+        assert!(!module.semantically_eq(&with_features));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_spans() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+
+        /// A subscriber collecting the fields recorded on any span.
+        #[derive(Clone, Default)]
+        struct Fields(Arc<Mutex<Vec<(String, String)>>>);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                let field = (field.name().to_string(), format!("{:?}", value));
+                self.0.lock().unwrap().push(field);
+            }
+        }
+
+        impl tracing::Subscriber for Fields {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes) -> Id {
+                span.record(&mut self.clone());
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, values: &Record) {
+                values.record(&mut self.clone());
+            }
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let mut module = Module::read(&wat2wasm!(
+            r#"(module (func (export "main") (block (nop))))"#
+        ))
+        .unwrap();
+        let before_bytes = module.write().len();
+        let fields = Fields::default();
+        tracing::subscriber::with_default(fields.clone(), || {
+            module
+                .run_optimization_passes(["vacuum", "merge-blocks"], &CodegenConfig::default())
+                .unwrap();
+        });
+
+        let fields = fields.0.lock().unwrap();
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value.clone())
+        };
+        assert_eq!(field("passes"), Some("\"vacuum,merge-blocks\"".to_string()));
+        assert_eq!(field("before_bytes"), Some(before_bytes.to_string()));
+        assert_eq!(field("after_bytes"), Some(module.write().len().to_string()));
+        assert!(field("duration_us").is_some());
+    }
+
+    #[test]
+    fn test_optimize_with_outcome() {
+        let input = wat2wasm!(
+            r#"(module (func (export "main") (result i32) (i32.add (i32.const 1) (i32.const 2))))"#
+        );
+        let mut module = Module::read(&input).unwrap();
+        let config = CodegenConfig {
+            optimization_level: 2,
+            ..CodegenConfig::default()
+        };
+        let before_bytes = module.write().len();
+
+        let outcome = module.optimize_with_outcome(&config);
+        assert!(outcome.changed);
+        assert_eq!(outcome.before_bytes, before_bytes);
+        assert!(outcome.after_bytes < outcome.before_bytes);
+        assert_eq!(outcome.after_bytes, module.write().len());
+
+        let outcome = module.optimize_with_outcome(&config);
+        assert!(!outcome.changed);
+        assert_eq!(outcome.before_bytes, outcome.after_bytes);
+    }
+
+    #[test]
+    fn test_write_with_source_map_mode() {
+        let module = Module::read(&wat2wasm!(r#"(module (func (export "main")))"#)).unwrap();
+        let contains = |haystack: &[u8], needle: &[u8]| {
+            haystack
+                .windows(needle.len())
+                .any(|window| window == needle)
+        };
+
+        let (binary, source_map) = module
+            .write_with_source_map_mode(&SourceMapMode::None)
+            .unwrap();
+        assert_eq!(binary, module.write());
+        assert_eq!(source_map, None);
+
+        let (binary, source_map) = module
+            .write_with_source_map_mode(&SourceMapMode::External("main.wasm.map".into()))
+            .unwrap();
+        assert!(contains(&binary, b"sourceMappingURL"));
+        assert!(contains(&binary, b"main.wasm.map"));
+        assert!(source_map.unwrap().starts_with('{'));
+        assert!(Module::read(&binary).is_ok());
+
+        let (binary, source_map) = module
+            .write_with_source_map_mode(&SourceMapMode::Inline)
+            .unwrap();
+        assert!(contains(&binary, b"sourceMappingURL"));
+        assert!(contains(&binary, b"data:application/json;base64,"));
+        assert_eq!(source_map, None);
+        assert!(Module::read(&binary).is_ok());
+
+        let err = module
+            .write_with_source_map_mode(&SourceMapMode::External("main\0.map".into()))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        const CODE: &'static str = r#"
+            (module
+                (func $add (export "add") (param i32 i32) (result i32)
+                    ;; comments don't survive normalization
+                    (i32.add (local.get 0) (local.get 1))
+                )
+            )
+        "#;
+        let module = Module::parse_text(CODE).unwrap();
+        module.assert_valid();
+        let text = module.write_text();
+        assert!(text.contains("i32.add"));
+        assert!(!text.contains("comments"));
+
+        // Normalizing is idempotent and agnostic of the formatting.
+        let normalized = normalize_wat(CODE).unwrap();
+        assert_eq!(normalize_wat(&normalized).unwrap(), normalized);
+        assert_eq!(
+            normalize_wat(
+                r#"(module (func $add (export "add") (param i32 i32) (result i32) local.get 0 local.get 1 i32.add))"#
+            )
+            .unwrap(),
+            normalized
+        );
+
+        // The binary form agrees with the text form.
+        let binary = Module::read(&wat2wasm!(CODE)).unwrap();
+        assert_eq!(binary.write(), module.write());
+    }
+
+    #[test]
+    fn test_text_features() {
+        const CODE: &'static str = r#"
+            (module
+                (func $splat (param i32) (result v128)
+                    (i32x4.splat (local.get 0))
+                )
+            )
+        "#;
+        let mut module = Module::parse_text(CODE).unwrap();
+        module.set_features(Features::SIMD | Features::BULK_MEMORY);
+        let text = module.write_text();
+        assert!(
+            text.starts_with(";; features: bulk-memory,simd\n(module"),
+            "{}",
+            text
+        );
+
+        let reparsed = Module::parse_text(&text).unwrap();
+        assert_eq!(reparsed.features(), Features::SIMD | Features::BULK_MEMORY);
+        assert_eq!(reparsed.validate_with_features(reparsed.features()), Ok(()));
+        assert_eq!(reparsed.write_text(), text);
+
+        module.set_features(Features::MVP);
+        assert!(module.write_text().starts_with("(module"));
+
+        let err = Module::parse_text(";; features: bogus\n(module)")
+            .err()
+            .unwrap();
+        assert_eq!(err.message(), "unknown feature: bogus");
+    }
+
+    #[test]
+    fn test_parse_text_error() {
+        let err = Module::parse_text("(module (func $f (i32.bogus)))")
+            .err()
+            .unwrap();
+        assert!(!err.message().is_empty());
+        assert!(Module::parse_text("").is_err());
+        assert!(Module::parse_text("(module").is_err());
+        assert!(normalize_wat("(module\0)").is_err());
+    }
+
+    #[test]
+    fn test_optimize_converge() {
+        const CODE: &'static str = r#"
+            (module
+                (func $helper (param i32) (result i32)
+                    (i32.add (local.get 0) (i32.const 1))
+                )
+                (func (export "main") (result i32)
+                    (call $helper (call $helper (i32.const 1)))
+                )
+            )
+        "#;
+        let config = OptLevel::O3.to_codegen_config();
+        let mut module = Module::read(&wat2wasm!(CODE)).unwrap();
+        assert_eq!(module.optimize_converge(&config, 0), 0);
+
+        let iterations = module.optimize_converge(&config, 10);
+        assert!((1..=10).contains(&iterations));
+        module.assert_valid();
+    }
+
+    #[test]
+    fn test_optimize_file() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("binaryen-rs-{}-input.wasm", std::process::id()));
+        let output = dir.join(format!("binaryen-rs-{}-output.wasm", std::process::id()));
+
+        let wasm = wat2wasm!(r#"(module (func (export "main") (drop (i32.const 1))))"#);
+        fs::write(&input, &wasm).unwrap();
+        optimize_file(&input, &output, &OptLevel::O2.to_codegen_config()).unwrap();
+        let optimized = fs::read(&output).unwrap();
+        assert!(optimized.len() < wasm.len());
+        Module::read(&optimized).unwrap().assert_valid();
+
+        fs::write(&input, b"\0asm\x01\0\0\0\x01\x7f").unwrap();
+        let err = optimize_file(&input, &output, &CodegenConfig::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let missing = dir.join("binaryen-rs-missing-input.wasm");
+        let err = optimize_file(&missing, &output, &CodegenConfig::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        fs::remove_file(&input).unwrap();
+        fs::remove_file(&output).unwrap();
+    }
+
+    #[test]
+    fn test_wat_file() {
+        let path = std::env::temp_dir().join(format!("binaryen-rs-{}.wat", std::process::id()));
+
+        let module = Module::parse_text(r#"(module (func (export "main") (nop)))"#).unwrap();
+        module.to_wat_file(&path).unwrap();
+        let read = Module::from_wat_file(&path).unwrap();
+        assert_eq!(read.write(), module.write());
+
+        fs::write(&path, "(module (func").unwrap();
+        let err = Module::from_wat_file(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.get_ref().unwrap().is::<ParseError>());
+
+        fs::remove_file(&path).unwrap();
+        let err = Module::from_wat_file(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_write_with_options() {
+        let module =
+            Module::read(&wat2wasm!(r#"(module (func $main (export "main") (nop)))"#)).unwrap();
+        let contains = |haystack: &[u8], needle: &[u8]| {
+            haystack
+                .windows(needle.len())
+                .any(|window| window == needle)
+        };
+
+        let (binary, source_map) = module.write_with_options(&WriteOptions::default()).unwrap();
+        assert_eq!(binary, module.write());
+        assert_eq!(source_map, None);
+
+        let options = WriteOptions {
+            debug_info: true,
+            source_map_url: Some("main.wasm.map".to_string()),
+            ..WriteOptions::default()
+        };
+        let (binary, source_map) = module.write_with_options(&options).unwrap();
+        assert!(contains(&binary, b"main.wasm.map"));
+        assert!(source_map.is_some());
+        let names = Module::read_header(&binary)
+            .unwrap()
+            .section(&SectionKind::Custom("name".to_string()))
+            .is_some();
+        assert!(names);
+
+        let path = std::env::temp_dir().join(format!("binaryen-rs-{}.symbols", std::process::id()));
+        let options = WriteOptions {
+            symbol_map: Some(path.clone()),
+            ..WriteOptions::default()
+        };
+        let (binary, _) = module.write_with_options(&options).unwrap();
+        assert_eq!(binary, module.write());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "0:main\n");
+        fs::remove_file(&path).unwrap();
+
+        let options = WriteOptions {
+            symbol_map: Some(path.join("missing-dir")),
+            ..WriteOptions::default()
+        };
+        assert!(module.write_with_options(&options).is_err());
+
+        let options = WriteOptions {
+            source_map_url: Some("main\0.map".to_string()),
+            ..WriteOptions::default()
+        };
+        let err = module.write_with_options(&options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
     #[test]
     fn test_smoke_optimize() {
         let input: Vec<u8> = vec![
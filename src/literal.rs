@@ -0,0 +1,153 @@
+use crate::{Expr, Module, ValueTy};
+
+/// A constant value, see [`Module::const_`].
+///
+/// Float values are handed to Binaryen by their bits, so `-0.0`, subnormals and the payload
+/// and signaling bit of NaNs are kept exactly. Note that `PartialEq` compares floats by value,
+/// e.g. NaNs are never equal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Literal {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    /// The bytes of a `v128`, in little-endian lane order. Needs the SIMD feature.
+    V128([u8; 16]),
+}
+
+impl Literal {
+    /// The type of the value.
+    pub fn ty(&self) -> ValueTy {
+        match self {
+            Literal::I32(_) => ValueTy::I32,
+            Literal::I64(_) => ValueTy::I64,
+            Literal::F32(_) => ValueTy::F32,
+            Literal::F64(_) => ValueTy::F64,
+            Literal::V128(_) => ValueTy::V128,
+        }
+    }
+
+    fn to_raw(self) -> binaryen_sys::BinaryenLiteral {
+        unsafe {
+            match self {
+                Literal::I32(value) => binaryen_sys::BinaryenLiteralInt32(value),
+                Literal::I64(value) => binaryen_sys::BinaryenLiteralInt64(value),
+                // Passing floats by value may quiet signaling NaNs on some targets, e.g. with x87.
+                Literal::F32(value) => {
+                    binaryen_sys::BinaryenLiteralFloat32Bits(value.to_bits() as i32)
+                }
+                Literal::F64(value) => {
+                    binaryen_sys::BinaryenLiteralFloat64Bits(value.to_bits() as i64)
+                }
+                Literal::V128(bytes) => binaryen_sys::BinaryenLiteralVec128(bytes.as_ptr()),
+            }
+        }
+    }
+}
+
+impl Module {
+    /// Create a `const` expression of the type of `value`, e.g. `f64.const`.
+    ///
+    /// ```
+    /// # use binaryen::{Literal, Module};
+    /// let module = Module::new();
+    /// let value = module.const_(Literal::F64(0.5));
+    /// assert_eq!(value.to_text().trim(), "(f64.const 0.5)");
+    /// ```
+    pub fn const_(&self, value: Literal) -> Expr {
+        let raw = unsafe { binaryen_sys::BinaryenConst(self.inner.raw, value.to_raw()) };
+        Expr::from_raw(self, raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Feature, Features, Ty};
+
+    #[test]
+    fn test_const() {
+        let mut module = Module::new();
+        module.set_features(Features::from(Feature::Simd));
+
+        let literals = [
+            Literal::I32(-1),
+            Literal::I64(i64::MAX),
+            Literal::F32(1.5),
+            Literal::F64(-2.25),
+            Literal::V128([1; 16]),
+        ];
+        for (i, literal) in literals.iter().enumerate() {
+            let body = module.const_(*literal);
+            let results = Ty::value(literal.ty());
+            module.add_fn(&format!("f{}", i), Ty::none(), results, &[], body);
+        }
+        module.assert_valid();
+
+        let text = module.write_text();
+        for instruction in [
+            "(i32.const -1)",
+            "(i64.const 9223372036854775807)",
+            "(f32.const 1.5)",
+            "(f64.const -2.25)",
+            "(v128.const i32x4 0x01010101 0x01010101 0x01010101 0x01010101)",
+        ] {
+            assert!(text.contains(instruction), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_float_bits_survive() {
+        let f32_bits = [
+            (-0.0f32).to_bits(),
+            // Signaling NaN, quiet NaN with a payload and negative quiet NaN.
+            0x7fa0_0001,
+            0x7fc0_1234,
+            0xffc0_0000,
+            // Smallest and largest subnormal.
+            0x0000_0001,
+            0x007f_ffff,
+        ];
+        let f64_bits = [
+            (-0.0f64).to_bits(),
+            0x7ff4_0000_0000_0001,
+            0x7ff8_0000_0000_abcd,
+            0xfff8_0000_0000_0000,
+            0x0000_0000_0000_0001,
+            0x000f_ffff_ffff_ffff,
+        ];
+
+        let mut module = Module::new();
+        for (i, bits) in f32_bits.iter().enumerate() {
+            let body = module.const_(Literal::F32(f32::from_bits(*bits)));
+            let results = ValueTy::F32.into();
+            module.add_fn(&format!("f32_{}", i), Ty::none(), results, &[], body);
+        }
+        for (i, bits) in f64_bits.iter().enumerate() {
+            let body = module.const_(Literal::F64(f64::from_bits(*bits)));
+            let results = ValueTy::F64.into();
+            module.add_fn(&format!("f64_{}", i), Ty::none(), results, &[], body);
+        }
+        module.assert_valid();
+
+        let contains = |haystack: &[u8], needle: &[u8]| {
+            haystack
+                .windows(needle.len())
+                .any(|window| window == needle)
+        };
+        for binary in [
+            module.write(),
+            Module::read(&module.write()).unwrap().write(),
+        ] {
+            for bits in &f32_bits {
+                // `f32.const` followed by the bits in little endian.
+                let encoded = [&[0x43][..], &bits.to_le_bytes()].concat();
+                assert!(contains(&binary, &encoded), "lost f32 {:#x}", bits);
+            }
+            for bits in &f64_bits {
+                let encoded = [&[0x44][..], &bits.to_le_bytes()].concat();
+                assert!(contains(&binary, &encoded), "lost f64 {:#x}", bits);
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use crate::encoding::read_leb128_u32;
+use crate::{name_from_raw, Module, SectionKind};
+use std::cmp::Reverse;
+use std::ffi::CStr;
+use std::fmt;
+
+/// Check whether a function is imported rather than defined by the module.
+unsafe fn is_imported(func: binaryen_sys::BinaryenFunctionRef) -> bool {
+    let module = binaryen_sys::BinaryenFunctionImportGetModule(func);
+    !module.is_null() && !CStr::from_ptr(module).to_bytes().is_empty()
+}
+
+/// Size and shape statistics of a module, similar to the output of Binaryen's `metrics` pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModuleMetrics {
+    /// Number of functions defined by the module.
+    pub functions: usize,
+    /// Number of imported functions.
+    pub imported_functions: usize,
+    /// Number of globals, including imported ones.
+    pub globals: usize,
+    /// Number of exports.
+    pub exports: usize,
+    /// Number of data segments.
+    pub data_segments: usize,
+    /// Number of instructions in the bodies of the defined functions.
+    pub total_instructions: usize,
+    /// Size of the module in the binary format, without debug info.
+    pub binary_bytes: usize,
+}
+
+impl ModuleMetrics {
+    /// Compute the change from these metrics to `other`.
+    ///
+    /// Positive deltas mean that `other` is bigger.
+    pub fn diff(&self, other: &ModuleMetrics) -> MetricsDiff {
+        let delta = |before: usize, after: usize| after as i64 - before as i64;
+        MetricsDiff {
+            functions: delta(self.functions, other.functions),
+            imported_functions: delta(self.imported_functions, other.imported_functions),
+            globals: delta(self.globals, other.globals),
+            exports: delta(self.exports, other.exports),
+            data_segments: delta(self.data_segments, other.data_segments),
+            total_instructions: delta(self.total_instructions, other.total_instructions),
+            binary_bytes: delta(self.binary_bytes, other.binary_bytes),
+        }
+    }
+}
+
+/// The change between two [`ModuleMetrics`], field by field.
+///
+/// Formatting it with `Display` lists the non-zero changes, e.g.
+/// `+1.2 KiB, +40 instructions, -1 functions`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetricsDiff {
+    pub functions: i64,
+    pub imported_functions: i64,
+    pub globals: i64,
+    pub exports: i64,
+    pub data_segments: i64,
+    pub total_instructions: i64,
+    pub binary_bytes: i64,
+}
+
+impl MetricsDiff {
+    /// Check whether none of the metrics changed.
+    pub fn is_empty(&self) -> bool {
+        *self == MetricsDiff::default()
+    }
+}
+
+fn format_bytes(bytes: i64) -> String {
+    if bytes.abs() < 1024 {
+        format!("{:+} bytes", bytes)
+    } else {
+        format!("{:+.1} KiB", bytes as f64 / 1024.0)
+    }
+}
+
+impl fmt::Display for MetricsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no change");
+        }
+        let counts = [
+            (self.total_instructions, "instructions"),
+            (self.functions, "functions"),
+            (self.imported_functions, "imported functions"),
+            (self.globals, "globals"),
+            (self.exports, "exports"),
+            (self.data_segments, "data segments"),
+        ];
+        let mut changes = vec![];
+        if self.binary_bytes != 0 {
+            changes.push(format_bytes(self.binary_bytes));
+        }
+        for (delta, what) in counts.iter().filter(|(delta, _)| *delta != 0) {
+            changes.push(format!("{:+} {}", delta, what));
+        }
+        write!(f, "{}", changes.join(", "))
+    }
+}
+
+impl Module {
+    /// Collect size and shape statistics of the module.
+    ///
+    /// This serializes the module to measure its binary size.
+    pub fn metrics(&self) -> ModuleMetrics {
+        let raw = self.inner.raw;
+        unsafe {
+            let num_functions = binaryen_sys::BinaryenGetNumFunctions(raw);
+            let imported_functions = (0..num_functions)
+                .map(|index| binaryen_sys::BinaryenGetFunctionByIndex(raw, index))
+                .filter(|&func| is_imported(func))
+                .count();
+
+            ModuleMetrics {
+                functions: num_functions as usize - imported_functions,
+                imported_functions,
+                globals: binaryen_sys::BinaryenGetNumGlobals(raw) as usize,
+                exports: binaryen_sys::BinaryenGetNumExports(raw) as usize,
+                data_segments: binaryen_sys::BinaryenGetNumMemorySegments(raw) as usize,
+                total_instructions: binaryen_sys::BinaryenShimCountInstructions(raw),
+                binary_bytes: self.write().len(),
+            }
+        }
+    }
+
+    /// List the functions defined by the module with the number of bytes each of them takes up
+    /// in the binary, biggest first.
+    ///
+    /// The size of a function is the size of its entry in the code section, i.e. its locals
+    /// and instructions, without the names section. Functions of the same size are listed in
+    /// module order. This serializes the module.
+    pub fn functions_by_size(&self) -> Vec<(String, usize)> {
+        let raw = self.inner.raw;
+        let names: Vec<&str> = unsafe {
+            (0..binaryen_sys::BinaryenGetNumFunctions(raw))
+                .map(|index| binaryen_sys::BinaryenGetFunctionByIndex(raw, index))
+                .filter(|&func| !is_imported(func))
+                .map(|func| name_from_raw(binaryen_sys::BinaryenFunctionGetName(func)))
+                .collect()
+        };
+
+        let binary = self.write();
+        let header = Module::read_header(&binary).expect("Binaryen writes well-formed modules");
+        let mut sizes = vec![];
+        if let Some(code) = header.section(&SectionKind::Code) {
+            let code = &binary[code.offset..code.offset + code.size];
+            let (count, mut pos) = read_leb128_u32(code).expect("code section has a count");
+            for _ in 0..count {
+                let (size, len) = read_leb128_u32(&code[pos..]).expect("function has a size");
+                sizes.push(len + size as usize);
+                pos += len + size as usize;
+            }
+        }
+        assert_eq!(names.len(), sizes.len(), "a body per defined function");
+
+        let mut functions: Vec<_> = names.into_iter().map(str::to_string).zip(sizes).collect();
+        functions.sort_by_key(|&(_, size)| Reverse(size));
+        functions
+    }
+
+    /// Compute how the metrics of this module changed compared to `before`.
+    pub fn metrics_delta(&self, before: &Module) -> MetricsDiff {
+        before.metrics().diff(&self.metrics())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let input = wat::parse_str(
+            r#"
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (global $g (mut i32) (i32.const 0))
+                (memory 1)
+                (data (i32.const 0) "hello")
+                (func $main (export "main")
+                    (call $log (global.get $g))
+                )
+            )
+            "#,
+        )
+        .unwrap();
+        let module = Module::read(&input).unwrap();
+        let metrics = module.metrics();
+        assert_eq!(
+            metrics,
+            ModuleMetrics {
+                functions: 1,
+                imported_functions: 1,
+                globals: 1,
+                exports: 1,
+                data_segments: 1,
+                total_instructions: 2,
+                binary_bytes: module.write().len(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_functions_by_size() {
+        let input = wat::parse_str(
+            r#"
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (func $small (export "small"))
+                (func $big (export "big")
+                    (call $log (i32.const 1))
+                    (call $log (i32.const 2))
+                )
+                (func $medium (export "medium")
+                    (call $log (i32.const 1))
+                )
+            )
+            "#,
+        )
+        .unwrap();
+        let module = Module::read(&input).unwrap();
+        let functions = module.functions_by_size();
+        let names: Vec<_> = functions.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["big", "medium", "small"]);
+        // The size prefix, no locals and `end`.
+        assert_eq!(functions[2].1, 3);
+
+        assert!(Module::new().functions_by_size().is_empty());
+    }
+
+    #[test]
+    fn test_metrics_diff() {
+        let before = ModuleMetrics {
+            functions: 10,
+            binary_bytes: 1000,
+            total_instructions: 100,
+            ..ModuleMetrics::default()
+        };
+        let after = ModuleMetrics {
+            functions: 9,
+            binary_bytes: 2229,
+            total_instructions: 140,
+            ..ModuleMetrics::default()
+        };
+        let diff = before.diff(&after);
+        assert_eq!(diff.functions, -1);
+        assert_eq!(diff.binary_bytes, 1229);
+        assert_eq!(diff.total_instructions, 40);
+        assert_eq!(diff.to_string(), "+1.2 KiB, +40 instructions, -1 functions");
+        assert_eq!(
+            after.diff(&before).to_string(),
+            "-1.2 KiB, -40 instructions, +1 functions"
+        );
+
+        assert!(before.diff(&before).is_empty());
+        assert_eq!(before.diff(&before).to_string(), "no change");
+
+        let small = ModuleMetrics {
+            binary_bytes: 990,
+            ..before
+        };
+        assert_eq!(before.diff(&small).to_string(), "-10 bytes");
+    }
+
+    #[test]
+    fn test_metrics_delta() {
+        let before =
+            Module::read(&wat::parse_str(r#"(module (func (export "a")))"#).unwrap()).unwrap();
+        let after = Module::read(
+            &wat::parse_str(r#"(module (func (export "a")) (func (export "b") (nop)))"#).unwrap(),
+        )
+        .unwrap();
+        let delta = after.metrics_delta(&before);
+        assert_eq!(delta.functions, 1);
+        assert_eq!(delta.exports, 1);
+        assert_eq!(delta.total_instructions, 1);
+        assert!(delta.binary_bytes > 0);
+        assert!(before.metrics_delta(&before).is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+/// A unary operator, see [`Module::unary`](crate::Module::unary).
+///
+/// The variants are named after the instructions they stand for, e.g. `TruncSF64ToI32` is
+/// `i32.trunc_f64_s`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    /// `i32.clz`
+    ClzI32,
+    /// `i32.ctz`
+    CtzI32,
+    /// `i32.popcnt`
+    PopcntI32,
+    /// `i32.eqz`
+    EqzI32,
+    /// `i64.clz`
+    ClzI64,
+    /// `i64.ctz`
+    CtzI64,
+    /// `i64.popcnt`
+    PopcntI64,
+    /// `i64.eqz`
+    EqzI64,
+    /// `f32.neg`
+    NegF32,
+    /// `f32.abs`
+    AbsF32,
+    /// `f32.ceil`
+    CeilF32,
+    /// `f32.floor`
+    FloorF32,
+    /// `f32.trunc`
+    TruncF32,
+    /// `f32.nearest`
+    NearestF32,
+    /// `f32.sqrt`
+    SqrtF32,
+    /// `f64.neg`
+    NegF64,
+    /// `f64.abs`
+    AbsF64,
+    /// `f64.ceil`
+    CeilF64,
+    /// `f64.floor`
+    FloorF64,
+    /// `f64.trunc`
+    TruncF64,
+    /// `f64.nearest`
+    NearestF64,
+    /// `f64.sqrt`
+    SqrtF64,
+    /// `i32.wrap_i64`
+    WrapI64,
+    /// `i64.extend_i32_s`
+    ExtendSI32,
+    /// `i64.extend_i32_u`
+    ExtendUI32,
+    /// `i32.trunc_f32_s`
+    TruncSF32ToI32,
+    /// `i32.trunc_f32_u`
+    TruncUF32ToI32,
+    /// `i32.trunc_f64_s`
+    TruncSF64ToI32,
+    /// `i32.trunc_f64_u`
+    TruncUF64ToI32,
+    /// `i64.trunc_f32_s`
+    TruncSF32ToI64,
+    /// `i64.trunc_f32_u`
+    TruncUF32ToI64,
+    /// `i64.trunc_f64_s`
+    TruncSF64ToI64,
+    /// `i64.trunc_f64_u`
+    TruncUF64ToI64,
+    /// `i32.trunc_sat_f32_s`
+    TruncSatSF32ToI32,
+    /// `i32.trunc_sat_f32_u`
+    TruncSatUF32ToI32,
+    /// `i32.trunc_sat_f64_s`
+    TruncSatSF64ToI32,
+    /// `i32.trunc_sat_f64_u`
+    TruncSatUF64ToI32,
+    /// `i64.trunc_sat_f32_s`
+    TruncSatSF32ToI64,
+    /// `i64.trunc_sat_f32_u`
+    TruncSatUF32ToI64,
+    /// `i64.trunc_sat_f64_s`
+    TruncSatSF64ToI64,
+    /// `i64.trunc_sat_f64_u`
+    TruncSatUF64ToI64,
+    /// `f32.convert_i32_s`
+    ConvertSI32ToF32,
+    /// `f32.convert_i32_u`
+    ConvertUI32ToF32,
+    /// `f32.convert_i64_s`
+    ConvertSI64ToF32,
+    /// `f32.convert_i64_u`
+    ConvertUI64ToF32,
+    /// `f64.convert_i32_s`
+    ConvertSI32ToF64,
+    /// `f64.convert_i32_u`
+    ConvertUI32ToF64,
+    /// `f64.convert_i64_s`
+    ConvertSI64ToF64,
+    /// `f64.convert_i64_u`
+    ConvertUI64ToF64,
+    /// `f64.promote_f32`
+    PromoteF32,
+    /// `f32.demote_f64`
+    DemoteF64,
+    /// `i32.reinterpret_f32`
+    ReinterpretF32,
+    /// `i64.reinterpret_f64`
+    ReinterpretF64,
+    /// `f32.reinterpret_i32`
+    ReinterpretI32,
+    /// `f64.reinterpret_i64`
+    ReinterpretI64,
+    /// `i32.extend8_s`
+    Extend8SI32,
+    /// `i32.extend16_s`
+    Extend16SI32,
+    /// `i64.extend8_s`
+    Extend8SI64,
+    /// `i64.extend16_s`
+    Extend16SI64,
+    /// `i64.extend32_s`
+    Extend32SI64,
+}
+
+impl UnaryOp {
+    pub(crate) fn to_raw(self) -> binaryen_sys::BinaryenOp {
+        unsafe {
+            match self {
+                UnaryOp::ClzI32 => binaryen_sys::BinaryenClzInt32(),
+                UnaryOp::CtzI32 => binaryen_sys::BinaryenCtzInt32(),
+                UnaryOp::PopcntI32 => binaryen_sys::BinaryenPopcntInt32(),
+                UnaryOp::EqzI32 => binaryen_sys::BinaryenEqZInt32(),
+                UnaryOp::ClzI64 => binaryen_sys::BinaryenClzInt64(),
+                UnaryOp::CtzI64 => binaryen_sys::BinaryenCtzInt64(),
+                UnaryOp::PopcntI64 => binaryen_sys::BinaryenPopcntInt64(),
+                UnaryOp::EqzI64 => binaryen_sys::BinaryenEqZInt64(),
+                UnaryOp::NegF32 => binaryen_sys::BinaryenNegFloat32(),
+                UnaryOp::AbsF32 => binaryen_sys::BinaryenAbsFloat32(),
+                UnaryOp::CeilF32 => binaryen_sys::BinaryenCeilFloat32(),
+                UnaryOp::FloorF32 => binaryen_sys::BinaryenFloorFloat32(),
+                UnaryOp::TruncF32 => binaryen_sys::BinaryenTruncFloat32(),
+                UnaryOp::NearestF32 => binaryen_sys::BinaryenNearestFloat32(),
+                UnaryOp::SqrtF32 => binaryen_sys::BinaryenSqrtFloat32(),
+                UnaryOp::NegF64 => binaryen_sys::BinaryenNegFloat64(),
+                UnaryOp::AbsF64 => binaryen_sys::BinaryenAbsFloat64(),
+                UnaryOp::CeilF64 => binaryen_sys::BinaryenCeilFloat64(),
+                UnaryOp::FloorF64 => binaryen_sys::BinaryenFloorFloat64(),
+                UnaryOp::TruncF64 => binaryen_sys::BinaryenTruncFloat64(),
+                UnaryOp::NearestF64 => binaryen_sys::BinaryenNearestFloat64(),
+                UnaryOp::SqrtF64 => binaryen_sys::BinaryenSqrtFloat64(),
+                UnaryOp::WrapI64 => binaryen_sys::BinaryenWrapInt64(),
+                UnaryOp::ExtendSI32 => binaryen_sys::BinaryenExtendSInt32(),
+                UnaryOp::ExtendUI32 => binaryen_sys::BinaryenExtendUInt32(),
+                UnaryOp::TruncSF32ToI32 => binaryen_sys::BinaryenTruncSFloat32ToInt32(),
+                UnaryOp::TruncUF32ToI32 => binaryen_sys::BinaryenTruncUFloat32ToInt32(),
+                UnaryOp::TruncSF64ToI32 => binaryen_sys::BinaryenTruncSFloat64ToInt32(),
+                UnaryOp::TruncUF64ToI32 => binaryen_sys::BinaryenTruncUFloat64ToInt32(),
+                UnaryOp::TruncSF32ToI64 => binaryen_sys::BinaryenTruncSFloat32ToInt64(),
+                UnaryOp::TruncUF32ToI64 => binaryen_sys::BinaryenTruncUFloat32ToInt64(),
+                UnaryOp::TruncSF64ToI64 => binaryen_sys::BinaryenTruncSFloat64ToInt64(),
+                UnaryOp::TruncUF64ToI64 => binaryen_sys::BinaryenTruncUFloat64ToInt64(),
+                UnaryOp::TruncSatSF32ToI32 => binaryen_sys::BinaryenTruncSatSFloat32ToInt32(),
+                UnaryOp::TruncSatUF32ToI32 => binaryen_sys::BinaryenTruncSatUFloat32ToInt32(),
+                UnaryOp::TruncSatSF64ToI32 => binaryen_sys::BinaryenTruncSatSFloat64ToInt32(),
+                UnaryOp::TruncSatUF64ToI32 => binaryen_sys::BinaryenTruncSatUFloat64ToInt32(),
+                UnaryOp::TruncSatSF32ToI64 => binaryen_sys::BinaryenTruncSatSFloat32ToInt64(),
+                UnaryOp::TruncSatUF32ToI64 => binaryen_sys::BinaryenTruncSatUFloat32ToInt64(),
+                UnaryOp::TruncSatSF64ToI64 => binaryen_sys::BinaryenTruncSatSFloat64ToInt64(),
+                UnaryOp::TruncSatUF64ToI64 => binaryen_sys::BinaryenTruncSatUFloat64ToInt64(),
+                UnaryOp::ConvertSI32ToF32 => binaryen_sys::BinaryenConvertSInt32ToFloat32(),
+                UnaryOp::ConvertUI32ToF32 => binaryen_sys::BinaryenConvertUInt32ToFloat32(),
+                UnaryOp::ConvertSI64ToF32 => binaryen_sys::BinaryenConvertSInt64ToFloat32(),
+                UnaryOp::ConvertUI64ToF32 => binaryen_sys::BinaryenConvertUInt64ToFloat32(),
+                UnaryOp::ConvertSI32ToF64 => binaryen_sys::BinaryenConvertSInt32ToFloat64(),
+                UnaryOp::ConvertUI32ToF64 => binaryen_sys::BinaryenConvertUInt32ToFloat64(),
+                UnaryOp::ConvertSI64ToF64 => binaryen_sys::BinaryenConvertSInt64ToFloat64(),
+                UnaryOp::ConvertUI64ToF64 => binaryen_sys::BinaryenConvertUInt64ToFloat64(),
+                UnaryOp::PromoteF32 => binaryen_sys::BinaryenPromoteFloat32(),
+                UnaryOp::DemoteF64 => binaryen_sys::BinaryenDemoteFloat64(),
+                UnaryOp::ReinterpretF32 => binaryen_sys::BinaryenReinterpretFloat32(),
+                UnaryOp::ReinterpretF64 => binaryen_sys::BinaryenReinterpretFloat64(),
+                UnaryOp::ReinterpretI32 => binaryen_sys::BinaryenReinterpretInt32(),
+                UnaryOp::ReinterpretI64 => binaryen_sys::BinaryenReinterpretInt64(),
+                UnaryOp::Extend8SI32 => binaryen_sys::BinaryenExtendS8Int32(),
+                UnaryOp::Extend16SI32 => binaryen_sys::BinaryenExtendS16Int32(),
+                UnaryOp::Extend8SI64 => binaryen_sys::BinaryenExtendS8Int64(),
+                UnaryOp::Extend16SI64 => binaryen_sys::BinaryenExtendS16Int64(),
+                UnaryOp::Extend32SI64 => binaryen_sys::BinaryenExtendS32Int64(),
+            }
+        }
+    }
+}
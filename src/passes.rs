@@ -0,0 +1,1343 @@
+//! Typed wrappers for individual Binaryen passes.
+
+use crate::{take_shim_string, CodegenConfig, Feature, Features, Module, ValidationError};
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::{error, fmt, ptr};
+
+/// Pass arguments in the form the shim takes them, as parallel arrays of names and values.
+///
+/// The arguments are handed to the shim with every run instead of being set globally, so
+/// passes running on different threads don't see each other's arguments.
+pub(crate) struct PassArguments {
+    pub(crate) names: Vec<*const c_char>,
+    pub(crate) values: Vec<*const c_char>,
+    // Owns the strings the pointers point to.
+    _strings: Vec<CString>,
+}
+
+impl PassArguments {
+    pub(crate) fn new(arguments: &[(&str, &str)]) -> PassArguments {
+        let mut strings = vec![];
+        for (name, value) in arguments {
+            strings.push(CString::new(*name).expect("pass argument names contain no NUL bytes"));
+            strings.push(CString::new(*value).expect("pass argument values contain no NUL bytes"));
+        }
+        PassArguments {
+            names: strings
+                .iter()
+                .step_by(2)
+                .map(|name| name.as_ptr())
+                .collect(),
+            values: strings
+                .iter()
+                .skip(1)
+                .step_by(2)
+                .map(|value| value.as_ptr())
+                .collect(),
+            _strings: strings,
+        }
+    }
+}
+
+/// Options for [`Module::asyncify`].
+#[derive(Clone, Debug, Default)]
+pub struct AsyncifyOptions {
+    /// The imports that may start an unwind or rewind, as `module.base` names.
+    ///
+    /// `None` assumes that any import may.
+    pub imports: Option<Vec<String>>,
+    /// Assume that no import starts an unwind or rewind, regardless of `imports`.
+    ///
+    /// Indirect calls are still instrumented.
+    pub ignore_imports: bool,
+    /// Functions that are instrumented even if the analysis thinks they don't need to be.
+    pub add_list: Vec<String>,
+    /// Functions that are never instrumented, even if the analysis thinks they need to be.
+    pub remove_list: Vec<String>,
+}
+
+/// Error returned by [`Module::extract_function`] and [`Module::extract_function_by_index`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExtractFunctionError {
+    /// There is no function with the given name.
+    NotFound(String),
+    /// There is no function with the given index.
+    IndexOutOfBounds(u32),
+    /// The function is imported, so there is no body to extract.
+    Imported(String),
+}
+
+impl fmt::Display for ExtractFunctionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExtractFunctionError::NotFound(name) => write!(f, "no function named `{}`", name),
+            ExtractFunctionError::IndexOutOfBounds(index) => {
+                write!(f, "no function with index {}", index)
+            }
+            ExtractFunctionError::Imported(name) => {
+                write!(f, "function `{}` is imported and can't be extracted", name)
+            }
+        }
+    }
+}
+
+impl error::Error for ExtractFunctionError {}
+
+/// Error returned by [`Module::limit_segments`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LimitSegmentsError {
+    /// The limit is zero, but data can't be kept without a segment.
+    ZeroLimit,
+    /// The segments couldn't be merged down to the limit, e.g. because some have non-constant
+    /// offsets or there are `max` passive segments already. The module is left unchanged.
+    CannotMerge {
+        /// The number of segments of the module.
+        segments: u32,
+        /// The limit that was asked for.
+        max: u32,
+    },
+}
+
+impl fmt::Display for LimitSegmentsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitSegmentsError::ZeroLimit => write!(f, "can't limit data segments to zero"),
+            LimitSegmentsError::CannotMerge { segments, max } => write!(
+                f,
+                "can't merge {} data segments into at most {}",
+                segments, max
+            ),
+        }
+    }
+}
+
+impl error::Error for LimitSegmentsError {}
+
+/// The renames done by [`Module::minify_imports_and_exports`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NameMap {
+    /// Maps the original base names of imports to the new ones.
+    ///
+    /// Imports with the same base name from different modules share the new name.
+    pub imports: BTreeMap<String, String>,
+    /// Maps the original export names to the new ones.
+    pub exports: BTreeMap<String, String>,
+}
+
+/// Parse the `index:name` lines printed by the `print-function-map` pass.
+fn parse_function_map(output: &str) -> Vec<(u32, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (index, name) = line.split_once(':')?;
+            Some((index.parse().ok()?, name.to_string()))
+        })
+        .collect()
+}
+
+/// Parse the `old => new` lines printed by the `minify-imports-and-exports` pass.
+fn parse_renames(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (old, new) = line.split_once(" => ")?;
+            Some((old.to_string(), new.to_string()))
+        })
+        .collect()
+}
+
+impl Module {
+    /// Run a single pass that is known to be registered, with the given pass arguments set for
+    /// this run only.
+    pub(crate) fn run_pass_with_arguments(&mut self, pass: &str, arguments: &[(&str, &str)]) {
+        self.run_optimization_passes_with_arguments(&[pass], arguments, &CodegenConfig::default())
+            .expect("pass is registered");
+    }
+
+    /// Run a single pass that is known to be registered and doesn't change the module,
+    /// returning what it prints instead of printing it.
+    pub(crate) fn run_pass_capturing_output(&self, pass: &str) -> String {
+        // The pass doesn't change the module, so a shared reference is enough.
+        unsafe { self.capture_pass_output(pass) }
+    }
+
+    /// Like [`Module::run_pass_capturing_output`], but for a pass that changes the module.
+    pub(crate) fn run_changing_pass_capturing_output(&mut self, pass: &str) -> String {
+        unsafe { self.capture_pass_output(pass) }
+    }
+
+    /// Run a single pass that is known to be registered and return what it prints.
+    ///
+    /// Only what the pass prints to stdout on the calling thread is captured, which is where
+    /// the whole-module passes used for this print. Anything else printing to stdout meanwhile,
+    /// e.g. passes on other threads, still goes to stdout and doesn't end up in the output.
+    ///
+    /// The caller has to make sure the module may be changed if the pass does so.
+    unsafe fn capture_pass_output(&self, pass: &str) -> String {
+        let pass = CString::new(pass).expect("pass names contain no NUL bytes");
+        let mut error = ptr::null_mut();
+        let output = binaryen_sys::BinaryenShimRunPassCapturingOutput(
+            self.inner.raw,
+            pass.as_ptr(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+            0,
+            &mut error,
+        );
+        if let Some(message) = take_shim_string(error) {
+            panic!("Binaryen hit a fatal error: {}", message.trim_end());
+        }
+        take_shim_string(output).unwrap_or_default()
+    }
+
+    /// Transform the module so that its execution can be paused and resumed.
+    ///
+    /// This adds the `asyncify_start_unwind`, `asyncify_stop_unwind`, `asyncify_start_rewind`,
+    /// `asyncify_stop_rewind` and `asyncify_get_state` exports. The module must have a memory
+    /// for the asyncify data.
+    pub fn asyncify(&mut self, opts: AsyncifyOptions) {
+        let imports = opts.imports.map(|imports| imports.join(","));
+        let add_list = opts.add_list.join(",");
+        let remove_list = opts.remove_list.join(",");
+
+        let mut arguments = vec![];
+        if let Some(imports) = &imports {
+            arguments.push(("asyncify-imports", imports.as_str()));
+        }
+        if opts.ignore_imports {
+            arguments.push(("asyncify-ignore-imports", ""));
+        }
+        if !opts.add_list.is_empty() {
+            arguments.push(("asyncify-addlist", add_list.as_str()));
+        }
+        if !opts.remove_list.is_empty() {
+            arguments.push(("asyncify-removelist", remove_list.as_str()));
+        }
+        self.run_pass_with_arguments("asyncify", &arguments);
+    }
+
+    /// Adapt the imports and exports that use `i64` to JavaScript hosts without BigInt
+    /// integration.
+    ///
+    /// Every `i64` parameter is split into two `i32` halves. An `i64` result is returned as its
+    /// low half, with the high half passed through `env.setTempRet0(i32)` and
+    /// `env.getTempRet0() -> i32`, which the host has to provide. Exports are replaced by
+    /// `legalstub$` wrappers and imports are called through `legalfunc$` wrappers.
+    ///
+    /// With `enforce` every import and export is legalized. Without it only the ones JS is
+    /// sure to see are, which Emscripten uses for its minimal runtime.
+    pub fn legalize_js_interface(&mut self, enforce: bool) {
+        let pass = if enforce {
+            "legalize-js-interface"
+        } else {
+            "legalize-js-interface-minimally"
+        };
+        self.run_pass_with_arguments(pass, &[]);
+    }
+
+    /// Adapt the module to the JavaScript Promise Integration API, so that imports returning
+    /// promises suspend the wasm code instead of blocking.
+    ///
+    /// The suspending `imports` are given as `module.base` names and the `exports` that may
+    /// suspend by their names. An empty list stands for all imports or exports. The wrapped
+    /// imports and exports take an additional `externref` suspender as their first parameter,
+    /// so the reference types feature is enabled on the module.
+    pub fn apply_jspi(&mut self, imports: &[&str], exports: &[&str]) {
+        self.set_features(self.features() | Features::REFERENCE_TYPES);
+        let imports = imports.join(",");
+        let exports = exports.join(",");
+
+        let mut arguments = vec![];
+        if !imports.is_empty() {
+            arguments.push(("jspi-imports", imports.as_str()));
+        }
+        if !exports.is_empty() {
+            arguments.push(("jspi-exports", exports.as_str()));
+        }
+        self.run_pass_with_arguments("jspi", &arguments);
+    }
+
+    /// Optimize the layout of data segments.
+    ///
+    /// This drops zero-filled ranges from segments, splitting them where it pays off, and
+    /// removes segments that are never used.
+    pub fn pack_memory(&mut self) {
+        self.run_pass_with_arguments("memory-packing", &[]);
+    }
+
+    /// Merge data segments so that the module has at most `max` of them.
+    ///
+    /// Passive segments are kept as they are. The active segments with the highest offsets are
+    /// merged into one, with the gaps between them filled with zeros, and `data.drop`,
+    /// `memory.init` and the like referring to them are redirected to the merged segment. As
+    /// active segments are dropped on instantiation, this doesn't change what they do.
+    /// Binaryen's `limit-segments` pass only applies the fixed limit of web engines, so the
+    /// merging is done directly.
+    ///
+    /// Fails, leaving the module unchanged, if `max` is zero or if the segments can't be merged:
+    /// that is the case if active segments have non-constant or overlapping offsets or belong
+    /// to different memories, or if there are `max` passive segments or more.
+    pub fn limit_segments(&mut self, max: u32) -> Result<(), LimitSegmentsError> {
+        if max == 0 {
+            return Err(LimitSegmentsError::ZeroLimit);
+        }
+        let segments = unsafe { binaryen_sys::BinaryenGetNumMemorySegments(self.inner.raw) };
+        match unsafe { binaryen_sys::BinaryenShimLimitDataSegments(self.inner.raw, max) } {
+            1 => Ok(()),
+            _ => Err(LimitSegmentsError::CannotMerge { segments, max }),
+        }
+    }
+
+    /// Lower all 64-bit integer operations to 32-bit ones, for hosts without `i64` support.
+    ///
+    /// Every `i64` value is split into two `i32` halves, including function parameters and
+    /// results. The high half of a function's result is returned through the
+    /// `i64toi32_i32$HIGH_BITS` global. The module is flattened first, as the lowering requires.
+    ///
+    /// The result is validated, since the lowering doesn't support every construct (e.g. `i64`
+    /// atomics). Nothing is rolled back if validation fails.
+    pub fn lower_i64_to_i32(&mut self) -> Result<(), ValidationError> {
+        self.run_optimization_passes(
+            ["flatten", "i64-to-i32-lowering"],
+            &CodegenConfig::default(),
+        )
+        .expect("passes are registered");
+        self.validate()
+    }
+
+    /// Lower 64-bit memories to 32-bit ones, for hosts without memory64 support.
+    ///
+    /// Addresses are wrapped to `i32` before every memory access, and the memory declarations
+    /// are turned into 32-bit ones. The result is validated like in
+    /// [`Module::lower_i64_to_i32`], and nothing is rolled back if validation fails.
+    pub fn lower_memory64(&mut self) -> Result<(), ValidationError> {
+        self.run_pass_with_arguments("memory64-lowering", &[]);
+        self.validate()
+    }
+
+    /// Lower the sign-extension operators, such as `i32.extend8_s`, to shifts, for engines
+    /// that predate them.
+    ///
+    /// The sign-ext feature is disabled on the module afterwards. Use
+    /// [`Module::uses_sign_ext`] to check whether lowering is needed at all.
+    pub fn lower_sign_ext(&mut self) {
+        // The pass does nothing unless the feature is enabled, which isn't the case for modules
+        // read without a `target_features` section.
+        self.set_features(self.features() | Features::SIGN_EXT);
+        self.run_pass_with_arguments("signext-lowering", &[]);
+    }
+
+    /// Check whether the module uses any of the sign-extension operators.
+    ///
+    /// A shorthand for `uses_feature(Feature::SignExt)`, see [`Module::uses_feature`].
+    pub fn uses_sign_ext(&self) -> bool {
+        self.uses_feature(Feature::SignExt)
+    }
+
+    /// Generate `dynCall_*` thunks for calling functions in the table by index.
+    ///
+    /// A thunk is exported for every signature of the functions in the table, named after the
+    /// Emscripten signature string, e.g. `dynCall_ii` for `(param i32) (result i32)`. With
+    /// `only_i64` only signatures that involve `i64` get a thunk, which is what JavaScript
+    /// hosts without BigInt integration need.
+    pub fn generate_dyncalls(&mut self, only_i64: bool) {
+        let pass = if only_i64 {
+            "generate-i64-dyncalls"
+        } else {
+            "generate-dyncalls"
+        };
+        self.run_pass_with_arguments(pass, &[]);
+    }
+
+    /// Merge functions with identical bodies, returning the number of functions removed.
+    ///
+    /// Calls and other references to a removed function are redirected to the function that
+    /// is kept.
+    pub fn dedup_functions(&mut self) -> usize {
+        let num_functions = |module: &Module| unsafe {
+            binaryen_sys::BinaryenGetNumFunctions(module.inner.raw) as usize
+        };
+        let before = num_functions(self);
+        self.run_pass_with_arguments("duplicate-function-elimination", &[]);
+        before - num_functions(self)
+    }
+
+    /// Optimize a module using GC types, enabling the GC and reference types features first.
+    ///
+    /// This runs the standard optimization passes, which include `heap2local` and, from
+    /// `optimization_level` 2 on, the GC type refinements. If `codegen_config.closed_world` is
+    /// set, `gufa` and `unsubtyping` run first, and the standard passes add `type-refining`,
+    /// `signature-pruning`, `gto` and `cfp`, all of which rely on knowing every use of a type.
+    pub fn gc_optimize(&mut self, codegen_config: &CodegenConfig) {
+        self.set_features(self.features() | Features::GC | Features::REFERENCE_TYPES);
+        if codegen_config.closed_world {
+            self.run_optimization_passes(["gufa", "unsubtyping"], codegen_config)
+                .expect("passes are registered");
+        }
+        self.optimize(codegen_config);
+    }
+
+    /// Remove everything the module doesn't use, returning the number of bytes saved in the
+    /// binary format.
+    ///
+    /// Functions, globals, tables, memories and segments that can't be reached from the exports
+    /// or the start function are removed, as well as unused block labels and branches.
+    /// Unused GC types are only removed by `remove-unused-types`, which needs a closed world
+    /// and is therefore not run here.
+    pub fn remove_unused(&mut self) -> usize {
+        let before = self.write().len();
+        self.run_optimization_passes(
+            [
+                "remove-unused-module-elements",
+                "remove-unused-brs",
+                "remove-unused-names",
+            ],
+            &CodegenConfig::default(),
+        )
+        .expect("passes are registered");
+        before.saturating_sub(self.write().len())
+    }
+
+    /// Remove the debugging and tooling metadata before shipping the module, returning the
+    /// number of bytes saved in the binary format.
+    ///
+    /// This drops the names and DWARF sections, the source map and debug locations, the
+    /// `producers` section and the `target_features` section. Other custom sections are kept.
+    pub fn strip_for_release(&mut self) -> usize {
+        let before = self.write().len();
+        self.run_optimization_passes(
+            [
+                "strip-debug",
+                "strip-dwarf",
+                "strip-producers",
+                "strip-target-features",
+            ],
+            &CodegenConfig::default(),
+        )
+        .expect("passes are registered");
+        before.saturating_sub(self.write().len())
+    }
+
+    /// Remove calls to functions that only do something the first time they run, returning
+    /// the number of instructions removed.
+    ///
+    /// Such functions are guarded by a global that they set on their first run, which is the
+    /// pattern emitted for e.g. static initializers.
+    pub fn reduce_once_calls(&mut self) -> usize {
+        let num_instructions = |module: &Module| unsafe {
+            binaryen_sys::BinaryenShimCountInstructions(module.inner.raw)
+        };
+        let before = num_instructions(self);
+        self.run_pass_with_arguments("once-reduction", &[]);
+        before.saturating_sub(num_instructions(self))
+    }
+
+    /// Give every heap type of the module a name, keeping existing names that are short.
+    ///
+    /// This makes the text format of GC modules readable, where unnamed types are only
+    /// referred to by index.
+    pub fn name_types(&mut self) {
+        self.run_pass_with_arguments("name-types", &[]);
+    }
+
+    /// Rename imports and exports to short names, returning the renames that were done.
+    ///
+    /// Only imports from the `env` module and from WASI modules are renamed. The host has to
+    /// provide the imports and look up the exports under the new names.
+    pub fn minify_imports_and_exports(&mut self) -> NameMap {
+        let import_bases = |module: &Module| -> Vec<String> {
+            let names = module.import_names();
+            names.iter().map(|(_, _, base)| base.to_string()).collect()
+        };
+        let imports_before = import_bases(self);
+        let exports_before: Vec<String> = self
+            .exports()
+            .map(|export| export.name().to_string())
+            .collect();
+        // The pass prints an `old => new` line for every rename, of imports and exports alike,
+        // and gives the same name the same new name wherever it occurs. So a name that is
+        // both an import base and an export is only taken as renamed where the new name shows
+        // up afterwards.
+        let renames =
+            parse_renames(&self.run_changing_pass_capturing_output("minify-imports-and-exports"));
+        let renamed = |names: Vec<String>, exists: &dyn Fn(&str) -> bool| {
+            names
+                .into_iter()
+                .filter_map(|old| Some((old.clone(), renames.get(&old)?.clone())))
+                .filter(|(old, new)| old != new && exists(new))
+                .collect()
+        };
+        let imports_after = import_bases(self);
+        NameMap {
+            imports: renamed(imports_before, &|new| {
+                imports_after.iter().any(|base| base == new)
+            }),
+            exports: renamed(exports_before, &|new| self.get_export(new).is_some()),
+        }
+    }
+
+    /// Extract a single function into a module of its own.
+    ///
+    /// The returned module exports the function under its name, and all other functions it
+    /// calls become imports. This module is left unchanged.
+    pub fn extract_function(&self, name: &str) -> Result<Module, ExtractFunctionError> {
+        let not_found = || ExtractFunctionError::NotFound(name.to_string());
+        let c_name = CString::new(name).map_err(|_| not_found())?;
+        // The pass aborts the process on unknown or imported functions, so check first.
+        unsafe {
+            let func = binaryen_sys::BinaryenGetFunction(self.inner.raw, c_name.as_ptr());
+            if func.is_null() {
+                return Err(not_found());
+            }
+            let import_module = binaryen_sys::BinaryenFunctionImportGetModule(func);
+            if !import_module.is_null() && !CStr::from_ptr(import_module).to_bytes().is_empty() {
+                return Err(ExtractFunctionError::Imported(name.to_string()));
+            }
+        }
+
+        let mut extracted = self.deep_clone();
+        extracted.run_pass_with_arguments("extract-function", &[("extract-function", name)]);
+        Ok(extracted)
+    }
+
+    /// Extract the function with the given index into a module of its own, like
+    /// [`Module::extract_function`].
+    ///
+    /// Indices count imported functions too, in the order of the function index space. This is
+    /// useful for modules without meaningful or unique names, e.g. ones made by the fuzzer.
+    pub fn extract_function_by_index(&self, index: u32) -> Result<Module, ExtractFunctionError> {
+        // The pass aborts the process on out of bounds indices or imported functions, so check
+        // first.
+        unsafe {
+            if index >= binaryen_sys::BinaryenGetNumFunctions(self.inner.raw) {
+                return Err(ExtractFunctionError::IndexOutOfBounds(index));
+            }
+            let func = binaryen_sys::BinaryenGetFunctionByIndex(self.inner.raw, index);
+            let import_module = binaryen_sys::BinaryenFunctionImportGetModule(func);
+            if !import_module.is_null() && !CStr::from_ptr(import_module).to_bytes().is_empty() {
+                let name = CStr::from_ptr(binaryen_sys::BinaryenFunctionGetName(func));
+                return Err(ExtractFunctionError::Imported(
+                    name.to_string_lossy().into_owned(),
+                ));
+            }
+        }
+
+        let mut extracted = self.deep_clone();
+        let index = index.to_string();
+        extracted.run_pass_with_arguments(
+            "extract-function-index",
+            &[("extract-function-index", &index)],
+        );
+        Ok(extracted)
+    }
+
+    /// Render the call graph of the module in the Graphviz DOT language.
+    ///
+    /// Each function is a node, and there is an edge from every function to each function it
+    /// calls directly. Functions that can be called indirectly, through the table, are marked
+    /// as such.
+    pub fn call_graph_dot(&self) -> String {
+        self.run_pass_capturing_output("print-call-graph")
+    }
+
+    /// List the index and name of every function of the module, imported ones included.
+    ///
+    /// This is the table needed to symbolicate profiles or stack traces, which refer to
+    /// functions by index.
+    pub fn function_map(&self) -> Vec<(u32, String)> {
+        parse_function_map(&self.run_pass_capturing_output("print-function-map"))
+    }
+
+    /// Write the symbol map of the module, as `wasm-opt --symbolmap` does.
+    ///
+    /// The map has an `index:name` line for every function, with the names the functions have
+    /// inside the module. So after [`Module::minify_imports_and_exports`] and stripping the
+    /// names section, e.g. with [`Module::strip_for_release`], it still turns the function
+    /// indices of stack traces into the original names. See [`Module::function_map`] for the
+    /// parsed form.
+    pub fn symbol_map(&self) -> String {
+        self.run_pass_capturing_output("symbolmap")
+    }
+
+    /// Trace the calls to the given imported functions.
+    ///
+    /// After each call to one of the `functions`, the instrumented module calls the import
+    /// `env.trace_<name>` with the arguments of the call followed by its result, if any. So
+    /// tracing `log: (i32) -> ()` needs the host to provide `env.trace_log: (i32) -> ()`.
+    pub fn trace_calls(&mut self, functions: &[&str]) {
+        let functions = functions.join(",");
+        self.run_pass_with_arguments("trace-calls", &[("trace-calls", &functions)]);
+    }
+
+    /// Instrument memory accesses with calls to host functions.
+    ///
+    /// Every load, store and `memory.grow` gets reported to imports from the `env` module. Each
+    /// call receives a unique `id` of the instrumented instruction as its first argument. The
+    /// `ptr` hooks must return the (possibly changed) address, and the `val` hooks the
+    /// (possibly changed) value:
+    ///
+    /// - `load_ptr(id: i32, bytes: i32, offset: i32, address: i32) -> i32`
+    /// - `load_val_i32(id: i32, value: i32) -> i32`, and likewise `load_val_i64`,
+    ///   `load_val_f32` and `load_val_f64` for the other value types
+    /// - `store_ptr(id: i32, bytes: i32, offset: i32, address: i32) -> i32`
+    /// - `store_val_i32(id: i32, value: i32) -> i32`, and likewise `store_val_i64`,
+    ///   `store_val_f32` and `store_val_f64`
+    /// - `memory_grow_pre(id: i32, delta: i32) -> i32`
+    /// - `memory_grow_post(id: i32, result: i32) -> i32`
+    ///
+    /// Addresses are `i64` instead of `i32` for 64-bit memories. The imports are added whether
+    /// or not the module has any memory accesses.
+    pub fn instrument_memory(&mut self) {
+        self.run_pass_with_arguments("instrument-memory", &[]);
+    }
+
+    /// Instrument local accesses with calls to host functions.
+    ///
+    /// Every `local.get` and `local.set` gets reported to imports from the `env` module. Each
+    /// call receives a unique `id` of the instrumented instruction and the local index, and
+    /// must return the (possibly changed) value:
+    ///
+    /// - `get_i32(id: i32, index: i32, value: i32) -> i32`, and likewise `get_i64`, `get_f32`
+    ///   and `get_f64`
+    /// - `set_i32(id: i32, index: i32, value: i32) -> i32`, and likewise `set_i64`, `set_f32`
+    ///   and `set_f64`
+    ///
+    /// Modules using SIMD or reference types additionally get variants for those types, e.g.
+    /// `get_v128` or `set_funcref`.
+    pub fn instrument_locals(&mut self) {
+        self.run_pass_with_arguments("instrument-locals", &[]);
+    }
+
+    /// Instrument function entries and loop headers with calls to a host function.
+    ///
+    /// Each instrumented location calls `env.log_execution(id: i32)` with a unique `id` of the
+    /// location.
+    pub fn log_execution(&mut self) {
+        self.run_pass_with_arguments("log-execution", &[]);
+    }
+
+    /// Guard memory accesses with bounds and alignment checks.
+    ///
+    /// Every load and store is replaced by a call to a generated `SAFE_HEAP_LOAD_*` or
+    /// `SAFE_HEAP_STORE_*` helper. The helpers report faults to imports from the `env` module:
+    ///
+    /// - `segfault()`, for null accesses and accesses past the end of the heap
+    /// - `alignfault()`, for unaligned accesses
+    ///
+    /// The end of the heap is read through `env.emscripten_get_sbrk_ptr() -> i32`, unless the
+    /// module already exports an `emscripten_get_sbrk_ptr` or `sbrk` function.
+    pub fn safe_heap(&mut self) {
+        self.run_pass_with_arguments("safe-heap", &[]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encoding, SectionKind, Ty, ValueTy};
+
+    const CODE: &'static str = r#"
+        (module
+            (memory 1)
+            (func $main (export "main") (param i32) (result i32)
+                (local $x i32)
+                (local.set $x (i32.load (local.get 0)))
+                (loop $l
+                    (i32.store (local.get 0) (local.get $x))
+                )
+                (local.get $x)
+            )
+        )
+    "#;
+
+    #[test]
+    fn test_pass_arguments() {
+        const FUNCTIONS: &'static str = r#"
+            (module
+                (func $a (export "a") (nop))
+                (func $b (export "b") (nop))
+            )
+        "#;
+        // `extract-function` fails without its argument, and keeps only the named function.
+        let mut module = Module::read(&wat::parse_str(FUNCTIONS).unwrap()).unwrap();
+        module.run_pass_with_arguments("extract-function", &[("extract-function", "b")]);
+        let exports: Vec<_> = module.exports().map(|export| export.value()).collect();
+        assert_eq!(exports, ["b"]);
+
+        // The arguments only apply to their own run, even if runs happen at the same time.
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let name = if i % 2 == 0 { "a" } else { "b" };
+                let module = Module::read(&wat::parse_str(FUNCTIONS).unwrap()).unwrap();
+                let owned = module.into_owned().ok().unwrap();
+                std::thread::spawn(move || {
+                    let module = owned.into_module();
+                    let extracted = module.extract_function(name).unwrap();
+                    let exports: Vec<_> = extracted
+                        .exports()
+                        .map(|export| export.value().to_string())
+                        .collect();
+                    assert_eq!(exports, [name]);
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_asyncify() {
+        const RECURSIVE: &'static str = r#"
+            (module
+                (import "env" "sleep" (func $sleep))
+                (import "env" "log" (func $log (param i32)))
+                (memory 1)
+                (func $count (export "count") (param i32)
+                    (call $log (local.get 0))
+                    (if (local.get 0)
+                        (then
+                            (call $sleep)
+                            (call $count (i32.sub (local.get 0) (i32.const 1)))
+                        )
+                    )
+                )
+            )
+        "#;
+        let options = AsyncifyOptions {
+            imports: Some(vec!["env.sleep".to_string()]),
+            ..AsyncifyOptions::default()
+        };
+        let mut module = Module::read(&wat::parse_str(RECURSIVE).unwrap()).unwrap();
+        module.asyncify(options);
+        module.assert_valid();
+        for name in [
+            "asyncify_start_unwind",
+            "asyncify_stop_unwind",
+            "asyncify_start_rewind",
+            "asyncify_stop_rewind",
+            "asyncify_get_state",
+        ] {
+            assert!(module.get_export(name).is_some(), "missing export {}", name);
+        }
+        let instrumented = module.write_text();
+
+        // Nothing can unwind if imports are ignored, so `count` is left alone.
+        let options = AsyncifyOptions {
+            ignore_imports: true,
+            ..AsyncifyOptions::default()
+        };
+        let mut module = Module::read(&wat::parse_str(RECURSIVE).unwrap()).unwrap();
+        module.asyncify(options);
+        module.assert_valid();
+        assert!(module.write_text().len() < instrumented.len());
+
+        // The arguments don't leak into later runs.
+        let mut module = Module::read(&wat::parse_str(RECURSIVE).unwrap()).unwrap();
+        module.asyncify(AsyncifyOptions::default());
+        assert!(module.write_text().len() >= instrumented.len());
+    }
+
+    fn num_data_segments(module: &Module) -> u32 {
+        unsafe { binaryen_sys::BinaryenGetNumMemorySegments(module.inner.raw) }
+    }
+
+    #[test]
+    fn test_pack_memory() {
+        const SPARSE: &'static str = r#"
+            (module
+                (memory 1)
+                (data (i32.const 0) "a\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00\00b")
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(SPARSE).unwrap()).unwrap();
+        let before = module.write().len();
+        module.pack_memory();
+        module.assert_valid();
+        assert!(module.write().len() < before);
+    }
+
+    #[test]
+    fn test_limit_segments() {
+        const SEGMENTS: &'static str = r#"
+            (module
+                (memory 1)
+                (data (i32.const 12) "d")
+                (data (i32.const 0) "a")
+                (data (i32.const 4) "b")
+                (data (i32.const 8) "c")
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(SEGMENTS).unwrap()).unwrap();
+        assert_eq!(num_data_segments(&module), 4);
+        module.limit_segments(4).unwrap();
+        assert_eq!(num_data_segments(&module), 4);
+
+        module.limit_segments(2).unwrap();
+        module.assert_valid();
+        assert_eq!(num_data_segments(&module), 2);
+        let text = module.write_text();
+        assert!(text.contains(r#""a""#), "{}", text);
+        assert!(text.contains(r#""b\00\00\00c\00\00\00d""#), "{}", text);
+
+        assert_eq!(module.limit_segments(0), Err(LimitSegmentsError::ZeroLimit));
+
+        // Passive segments are kept, and references to merged segments are redirected.
+        const MIXED: &'static str = r#"
+            (module
+                (memory 1)
+                (data $a (i32.const 0) "a")
+                (data $p "p")
+                (data $b (i32.const 4) "b")
+                (data $c (i32.const 8) "c")
+                (func (export "f")
+                    (data.drop $c)
+                    (memory.init $p (i32.const 0) (i32.const 0) (i32.const 1))
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(MIXED).unwrap()).unwrap();
+        module.limit_segments(2).unwrap();
+        module.assert_valid();
+        assert_eq!(num_data_segments(&module), 2);
+        let text = module.write_text();
+        assert!(text.contains(r#""a\00\00\00b\00\00\00c""#), "{}", text);
+        assert!(text.contains(r#"(data $p "p")"#), "{}", text);
+        assert!(text.contains("(data.drop $a)"), "{}", text);
+
+        const PASSIVE: &'static str = r#"
+            (module
+                (memory 1)
+                (data "a")
+                (data "b")
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(PASSIVE).unwrap()).unwrap();
+        assert_eq!(
+            module.limit_segments(1),
+            Err(LimitSegmentsError::CannotMerge {
+                segments: 2,
+                max: 1
+            })
+        );
+        assert_eq!(num_data_segments(&module), 2);
+    }
+
+    #[test]
+    fn test_generate_dyncalls() {
+        const TABLE: &'static str = r#"
+            (module
+                (table 2 funcref)
+                (elem (i32.const 0) $inc $drop_i64)
+                (func $inc (param i32) (result i32)
+                    (i32.add (local.get 0) (i32.const 1))
+                )
+                (func $drop_i64 (param i64))
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(TABLE).unwrap()).unwrap();
+        module.generate_dyncalls(false);
+        module.assert_valid();
+        assert!(module.get_export("dynCall_ii").is_some());
+        assert!(module.get_export("dynCall_vj").is_some());
+
+        let mut module = Module::read(&wat::parse_str(TABLE).unwrap()).unwrap();
+        module.generate_dyncalls(true);
+        module.assert_valid();
+        assert!(module.get_export("dynCall_ii").is_none());
+        assert!(module.get_export("dynCall_vj").is_some());
+    }
+
+    #[test]
+    fn test_dedup_functions() {
+        const DUPLICATES: &'static str = r#"
+            (module
+                (func $a (export "a") (result i32) (i32.const 42))
+                (func $b (export "b") (result i32) (i32.const 42))
+                (func $c (export "c") (result i32) (i32.const 42))
+                (func $d (export "d") (result i32) (i32.const 7))
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(DUPLICATES).unwrap()).unwrap();
+        assert_eq!(module.dedup_functions(), 2);
+        module.assert_valid();
+        assert_eq!(module.exports().count(), 4);
+        assert_eq!(module.dedup_functions(), 0);
+    }
+
+    #[test]
+    fn test_reduce_once_calls() {
+        const ONCE: &'static str = r#"
+            (module
+                (import "env" "init" (func $init_host))
+                (global $initialized (mut i32) (i32.const 0))
+                (func $init
+                    (if (global.get $initialized) (then (return)))
+                    (global.set $initialized (i32.const 1))
+                    (call $init_host)
+                )
+                (func (export "main")
+                    (call $init)
+                    (call $init)
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(ONCE).unwrap()).unwrap();
+        assert!(module.reduce_once_calls() > 0);
+        module.assert_valid();
+        assert_eq!(module.reduce_once_calls(), 0);
+    }
+
+    #[test]
+    fn test_name_types() {
+        const TYPES: &'static str = r#"
+            (module
+                (type (struct (field i32)))
+                (type (array i8))
+                (func (param (ref null 0) (ref null 1)))
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(TYPES).unwrap()).unwrap();
+        module.name_types();
+        module.assert_valid();
+        assert_eq!(module.type_names().len(), 3);
+    }
+
+    #[test]
+    fn test_minify_imports_and_exports() {
+        const NAMED: &'static str = r#"
+            (module
+                (import "env" "a_very_long_import_name" (func $log (param i32)))
+                (import "host" "kept" (func $kept))
+                (func (export "a_very_long_export_name") (param i32)
+                    (call $log (local.get 0))
+                    (call $kept)
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(NAMED).unwrap()).unwrap();
+        let map = module.minify_imports_and_exports();
+        module.assert_valid();
+
+        let import = &map.imports["a_very_long_import_name"];
+        assert!(import.len() < "a_very_long_import_name".len());
+        assert!(!map.imports.contains_key("kept"));
+        let imports: Vec<_> = module
+            .import_names()
+            .iter()
+            .map(|n| n.2.to_string())
+            .collect();
+        assert_eq!(imports, [import.as_str(), "kept"]);
+
+        let export = &map.exports["a_very_long_export_name"];
+        assert!(module.get_export(export).is_some());
+        assert!(module.get_export("a_very_long_export_name").is_none());
+
+        let renames = parse_renames("first => a\nnot a rename\nsecond => b\n");
+        assert_eq!(renames.len(), 2);
+        assert_eq!(renames["second"], "b");
+    }
+
+    #[test]
+    fn test_extract_function() {
+        const FUNCTIONS: &'static str = r#"
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (func $helper (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const 2))
+                )
+                (func $target (export "run") (param i32)
+                    (call $log (call $helper (local.get 0)))
+                )
+                (func $unrelated (export "other") (nop))
+            )
+        "#;
+        let module = Module::read(&wat::parse_str(FUNCTIONS).unwrap()).unwrap();
+        let before = module.write();
+
+        let extracted = module.extract_function("target").unwrap();
+        extracted.assert_valid();
+        assert_eq!(module.write(), before);
+
+        let exports: Vec<_> = extracted.exports().map(|export| export.value()).collect();
+        assert_eq!(exports, ["target"]);
+        assert_eq!(extracted.metrics().functions, 1);
+
+        assert_eq!(
+            module.extract_function("missing").err(),
+            Some(ExtractFunctionError::NotFound("missing".to_string()))
+        );
+        assert_eq!(
+            module.extract_function("log").err(),
+            Some(ExtractFunctionError::Imported("log".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_extract_function_by_index() {
+        const FUNCTIONS: &'static str = r#"
+            (module
+                (import "env" "log" (func (param i32)))
+                (func (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const 2))
+                )
+                (func (export "run") (param i32)
+                    (call 0 (call 1 (local.get 0)))
+                )
+            )
+        "#;
+        let module = Module::read(&wat::parse_str(FUNCTIONS).unwrap()).unwrap();
+        let before = module.write();
+
+        let extracted = module.extract_function_by_index(2).unwrap();
+        extracted.assert_valid();
+        assert_eq!(module.write(), before);
+        assert_eq!(extracted.exports().count(), 1);
+        assert_eq!(extracted.metrics().functions, 1);
+
+        assert_eq!(
+            module.extract_function_by_index(3).err(),
+            Some(ExtractFunctionError::IndexOutOfBounds(3))
+        );
+        assert!(matches!(
+            module.extract_function_by_index(0).err(),
+            Some(ExtractFunctionError::Imported(_))
+        ));
+    }
+
+    #[test]
+    fn test_lower_i64_to_i32() {
+        const I64: &'static str = r#"
+            (module
+                (func $add (param i64 i64) (result i64)
+                    (i64.add (local.get 0) (local.get 1))
+                )
+                (func (export "main") (result i32)
+                    (i32.wrap_i64 (call $add (i64.const 1) (i64.const 2)))
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(I64).unwrap()).unwrap();
+        module.lower_i64_to_i32().unwrap();
+
+        let text = module.write_text();
+        assert!(!text.contains("i64"), "{}", text);
+        assert!(text.contains("i64toi32_i32$HIGH_BITS"), "{}", text);
+    }
+
+    #[test]
+    fn test_lower_memory64() {
+        const MEMORY64: &'static str = r#"
+            (module
+                (memory i64 1)
+                (func (export "load") (param i64) (result i32)
+                    (i32.load (local.get 0))
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(MEMORY64).unwrap()).unwrap();
+        module.lower_memory64().unwrap();
+
+        let text = module.write_text();
+        assert!(text.contains("(memory $0 1)"), "{}", text);
+        assert!(text.contains("(i32.wrap_i64"), "{}", text);
+    }
+
+    #[test]
+    fn test_lower_sign_ext() {
+        const SIGN_EXT: &'static str = r#"
+            (module
+                (func (export "sext") (param i32) (result i32)
+                    (i32.extend8_s (local.get 0))
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(SIGN_EXT).unwrap()).unwrap();
+        assert!(module.uses_sign_ext());
+        module.lower_sign_ext();
+        module.assert_valid();
+        assert!(!module.uses_sign_ext());
+        assert!(!module.features().contains(Features::SIGN_EXT));
+        assert!(!module.write_text().contains("extend8_s"));
+
+        let module = Module::read(&wat::parse_str(CODE).unwrap()).unwrap();
+        assert!(!module.uses_sign_ext());
+    }
+
+    #[test]
+    fn test_gc_optimize() {
+        const GC: &'static str = r#"
+            (module
+                (type $point (struct (field $x (mut i32)) (field $y (mut i32))))
+                (func (export "sum") (param i32) (result i32)
+                    (local $p (ref null $point))
+                    (local.set $p (struct.new $point (local.get 0) (i32.const 2)))
+                    (i32.add
+                        (struct.get $point $x (local.get $p))
+                        (struct.get $point $y (local.get $p))
+                    )
+                )
+            )
+        "#;
+        for closed_world in [false, true] {
+            let mut module = Module::read(&wat::parse_str(GC).unwrap()).unwrap();
+            let config = CodegenConfig {
+                optimization_level: 2,
+                closed_world,
+                ..CodegenConfig::default()
+            };
+            module.gc_optimize(&config);
+            module.assert_valid();
+            assert!(module.features().contains(Features::GC));
+            assert!(!module.write_text().contains("struct.new"));
+        }
+    }
+
+    #[test]
+    fn test_legalize_js_interface() {
+        const I64: &'static str = r#"
+            (module
+                (func (export "add") (param i64 i64) (result i64)
+                    (i64.add (local.get 0) (local.get 1))
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(I64).unwrap()).unwrap();
+        module.legalize_js_interface(true);
+        module.assert_valid();
+
+        let text = module.write_text();
+        assert!(text.contains("legalstub$add"), "{}", text);
+        assert!(text.contains("(import \"env\" \"setTempRet0\""), "{}", text);
+    }
+
+    #[test]
+    fn test_apply_jspi() {
+        const ASYNC: &'static str = r#"
+            (module
+                (import "env" "fetch" (func $fetch (param i32) (result i32)))
+                (func (export "main") (param i32) (result i32)
+                    (call $fetch (local.get 0))
+                )
+                (func (export "other") (param i32) (result i32)
+                    (call $fetch (local.get 0))
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(ASYNC).unwrap()).unwrap();
+        let before = module.write_text();
+        module.apply_jspi(&["env.fetch"], &["main"]);
+        module.assert_valid();
+
+        let text = module.write_text();
+        assert_ne!(text, before);
+        assert!(text.contains("externref"), "{}", text);
+
+        // Only the listed export takes a suspender.
+        let params = |export: &str| {
+            let function = module.get_export(export).unwrap().value();
+            module.get_function(function).unwrap().params()
+        };
+        assert_ne!(params("main"), Ty::value(ValueTy::I32));
+        assert_eq!(params("other"), Ty::value(ValueTy::I32));
+    }
+
+    #[test]
+    fn test_remove_unused() {
+        const UNUSED: &'static str = r#"
+            (module
+                (global $unused (mut i32) (i32.const 0))
+                (func $dead (result i32) (i32.const 42))
+                (func (export "main")
+                    (block $out (nop))
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(UNUSED).unwrap()).unwrap();
+        let before = module.write().len();
+        let saved = module.remove_unused();
+        module.assert_valid();
+        assert!(saved > 0);
+        assert_eq!(module.write().len(), before - saved);
+        assert!(module.get_function("dead").is_none());
+        assert_eq!(module.remove_unused(), 0);
+    }
+
+    #[test]
+    fn test_strip_for_release() {
+        let mut binary = wat::parse_str(CODE).unwrap();
+        let mut target_features = vec![];
+        encoding::write_leb128_u32(&mut target_features, 1);
+        target_features.push(b'+');
+        encoding::write_name(&mut target_features, b"simd128");
+        encoding::append_custom_section(&mut binary, "target_features", &target_features);
+        encoding::append_custom_section(&mut binary, "mine", b"keep me");
+
+        let mut module = Module::read(&binary).unwrap();
+        module.set_producers(&[("processed-by", "mytool", "1.0")]);
+        assert!(module.target_features().is_some());
+
+        assert!(module.strip_for_release() > 0);
+        module.assert_valid();
+        assert_eq!(module.target_features(), None);
+
+        let header = Module::read_header(&module.write()).unwrap();
+        let custom = |name: &str| SectionKind::Custom(name.to_string());
+        assert!(header.section(&custom("producers")).is_none());
+        assert!(header.section(&custom("target_features")).is_none());
+        assert!(header.section(&custom("mine")).is_some());
+        assert_eq!(module.strip_for_release(), 0);
+    }
+
+    #[test]
+    fn test_trace_calls() {
+        const CALLS: &'static str = r#"
+            (module
+                (import "env" "add" (func $add (param i32 i32) (result i32)))
+                (import "env" "log" (func $log (param i32)))
+                (func (export "main") (result i32)
+                    (call $log (i32.const 1))
+                    (call $add (i32.const 1) (i32.const 2))
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(CALLS).unwrap()).unwrap();
+        module.trace_calls(&["add"]);
+        module.assert_valid();
+
+        let imports: Vec<_> = module
+            .import_names()
+            .iter()
+            .map(|n| n.2.to_string())
+            .collect();
+        assert!(imports.contains(&"trace_add".to_string()), "{:?}", imports);
+        assert!(!imports.contains(&"trace_log".to_string()), "{:?}", imports);
+    }
+
+    #[test]
+    fn test_safe_heap() {
+        const STORE: &'static str = r#"
+            (module
+                (memory 1)
+                (func (export "store") (param i32)
+                    (i32.store (local.get 0) (i32.const 42))
+                )
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(STORE).unwrap()).unwrap();
+        module.safe_heap();
+        module.assert_valid();
+
+        let text = module.write_text();
+        assert!(text.contains("(call $SAFE_HEAP_STORE_i32_4_4"), "{}", text);
+        assert!(!text.contains("(i32.store (local.get $0)"), "{}", text);
+        for name in ["segfault", "alignfault"] {
+            assert!(
+                text.contains(&format!("(import \"env\" \"{}\"", name)),
+                "missing {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_instrument_memory() {
+        let mut module = Module::read(&wat::parse_str(CODE).unwrap()).unwrap();
+        module.instrument_memory();
+        module.assert_valid();
+
+        let text = module.write_text();
+        for name in ["load_ptr", "load_val_i32", "store_ptr", "store_val_i32"] {
+            assert!(
+                text.contains(&format!("(import \"env\" \"{}\"", name)),
+                "missing import {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_instrument_locals() {
+        let mut module = Module::read(&wat::parse_str(CODE).unwrap()).unwrap();
+        module.instrument_locals();
+        module.assert_valid();
+
+        let text = module.write_text();
+        assert!(text.contains("(import \"env\" \"get_i32\""));
+        assert!(text.contains("(import \"env\" \"set_i32\""));
+    }
+
+    #[test]
+    fn test_log_execution() {
+        let mut module = Module::read(&wat::parse_str(CODE).unwrap()).unwrap();
+        module.log_execution();
+        module.assert_valid();
+
+        assert!(module
+            .write_text()
+            .contains("(import \"env\" \"log_execution\""));
+    }
+
+    #[test]
+    fn test_call_graph_dot() {
+        const CALLS: &'static str = r#"
+            (module
+                (func $helper (result i32) (i32.const 1))
+                (func $main (export "main") (result i32) (call $helper))
+            )
+        "#;
+        let module = Module::read(&wat::parse_str(CALLS).unwrap()).unwrap();
+        let before = module.write();
+
+        let dot = module.call_graph_dot();
+        assert!(dot.starts_with("digraph"), "{}", dot);
+        assert!(dot.contains("\"main\" -> \"helper\""), "{}", dot);
+        assert!(!dot.contains("\"helper\" -> "), "{}", dot);
+        assert_eq!(module.write(), before);
+    }
+
+    #[test]
+    fn test_function_map() {
+        const NAMES: &'static str = r#"
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (func $first)
+                (func $second (export "second"))
+            )
+        "#;
+        let module = Module::read(&wat::parse_str(NAMES).unwrap()).unwrap();
+        assert_eq!(
+            module.function_map(),
+            [
+                (0, "log".to_string()),
+                (1, "first".to_string()),
+                (2, "second".to_string())
+            ]
+        );
+        assert!(Module::new().function_map().is_empty());
+
+        assert_eq!(
+            parse_function_map("0:a\n1:b:c\nnot a line\n"),
+            [(0, "a".to_string()), (1, "b:c".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_symbol_map() {
+        const MINIFY: &'static str = r#"
+            (module
+                (import "env" "log_message" (func $log_message (param i32)))
+                (func $run (export "run_everything") (call $log_message (i32.const 0)))
+            )
+        "#;
+        let mut module = Module::read(&wat::parse_str(MINIFY).unwrap()).unwrap();
+        module.minify_imports_and_exports();
+
+        let map = module.symbol_map();
+        let lines: Vec<_> = map.lines().collect();
+        assert_eq!(lines, ["0:log_message", "1:run"]);
+        assert_eq!(parse_function_map(&map), module.function_map());
+    }
+}
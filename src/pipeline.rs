@@ -0,0 +1,235 @@
+use crate::{is_valid_pass, CodegenConfig, Features, Module, TransformError};
+use std::{error, fmt};
+
+/// Error returned when a pass name is not known to Binaryen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidPassError {
+    pass: String,
+}
+
+impl InvalidPassError {
+    pub(crate) fn new(pass: &str) -> InvalidPassError {
+        InvalidPassError {
+            pass: pass.to_string(),
+        }
+    }
+
+    /// The offending pass name.
+    pub fn pass(&self) -> &str {
+        &self.pass
+    }
+}
+
+impl fmt::Display for InvalidPassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "not a valid pass: {}", self.pass)
+    }
+}
+
+impl error::Error for InvalidPassError {}
+
+/// An ordered list of optimization passes together with the codegen configuration to run them
+/// with.
+///
+/// All pass names are checked on construction, including deserialization with the `serde`
+/// feature enabled, so a pipeline can always be applied.
+///
+/// ```
+/// # use binaryen::{CodegenConfig, PassPipeline};
+/// let pipeline = PassPipeline::new(&["vacuum", "untee"], CodegenConfig::default()).unwrap();
+/// assert!(PassPipeline::new(&["invalid"], CodegenConfig::default()).is_err());
+/// ```
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "UncheckedPassPipeline"))]
+pub struct PassPipeline {
+    passes: Vec<String>,
+    codegen_config: CodegenConfig,
+}
+
+impl PassPipeline {
+    /// Create a pipeline running `passes` in order.
+    ///
+    /// Returns `Err` if any of the passes is not known to Binaryen.
+    pub fn new<B: AsRef<str>, I: IntoIterator<Item = B>>(
+        passes: I,
+        codegen_config: CodegenConfig,
+    ) -> Result<PassPipeline, InvalidPassError> {
+        let mut checked = vec![];
+        for pass in passes {
+            let pass = pass.as_ref();
+            if !is_valid_pass(pass) {
+                return Err(InvalidPassError::new(pass));
+            }
+            checked.push(pass.to_string());
+        }
+        Ok(PassPipeline {
+            passes: checked,
+            codegen_config,
+        })
+    }
+
+    /// The passes of this pipeline, in the order they are run.
+    pub fn passes(&self) -> &[String] {
+        &self.passes
+    }
+
+    /// The configuration the passes are run with.
+    pub fn codegen_config(&self) -> &CodegenConfig {
+        &self.codegen_config
+    }
+
+    /// Run the passes of this pipeline on the module.
+    pub fn apply(&self, module: &mut Module) {
+        module
+            .run_optimization_passes(&self.passes, &self.codegen_config)
+            .expect("pass names are checked on construction");
+    }
+}
+
+/// Everything that goes into optimizing a module, see [`Module::apply_profile`].
+///
+/// Meant to be built from a configuration file: with the `serde` feature enabled, all fields
+/// are optional when deserializing.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct OptimizeProfile {
+    /// The configuration all passes are run with, see [`CodegenConfig::deterministic`] for
+    /// reproducible output.
+    pub codegen_config: CodegenConfig,
+    /// The features to enable on the module before optimizing. `None` keeps the module's
+    /// features.
+    pub features: Option<Features>,
+    /// Rerun the standard optimization passes until the module stops shrinking, at most this
+    /// many times, see [`Module::optimize_converge`]. `None` runs them once.
+    pub converge: Option<usize>,
+    /// Passes to run after the standard optimization passes.
+    pub extra_passes: Vec<String>,
+    /// Validate the module after optimizing.
+    pub validate: bool,
+}
+
+impl Module {
+    /// Optimize the module as described by `profile`.
+    ///
+    /// The extra pass names are checked first, and nothing is run if any of them is not known
+    /// to Binaryen. Nothing is rolled back if validation fails.
+    pub fn apply_profile(&mut self, profile: &OptimizeProfile) -> Result<(), TransformError> {
+        if let Some(pass) = profile
+            .extra_passes
+            .iter()
+            .find(|pass| !is_valid_pass(pass.as_str()))
+        {
+            return Err(InvalidPassError::new(pass).into());
+        }
+
+        if let Some(features) = profile.features {
+            self.set_features(features);
+        }
+        match profile.converge {
+            Some(max_iters) => {
+                self.optimize_converge(&profile.codegen_config, max_iters);
+            }
+            None => self.optimize(&profile.codegen_config),
+        }
+        self.run_optimization_passes(&profile.extra_passes, &profile.codegen_config)
+            .expect("pass names are checked above");
+        if profile.validate {
+            self.validate()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct UncheckedPassPipeline {
+    passes: Vec<String>,
+    #[serde(default)]
+    codegen_config: CodegenConfig,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedPassPipeline> for PassPipeline {
+    type Error = InvalidPassError;
+
+    fn try_from(unchecked: UncheckedPassPipeline) -> Result<PassPipeline, InvalidPassError> {
+        PassPipeline::new(unchecked.passes, unchecked.codegen_config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_pass() {
+        let err = PassPipeline::new(&["vacuum", "invalid"], CodegenConfig::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.pass(), "invalid");
+    }
+
+    #[test]
+    fn test_apply() {
+        let input =
+            wat::parse_str(r#"(module (func (export "main") (drop (i32.const 1))))"#).unwrap();
+        let mut module = Module::read(&input).unwrap();
+
+        let pipeline = PassPipeline::new(&["vacuum"], CodegenConfig::default()).unwrap();
+        assert_eq!(pipeline.passes(), ["vacuum"]);
+        pipeline.apply(&mut module);
+        module.assert_valid();
+    }
+
+    #[test]
+    fn test_apply_profile() {
+        let input = wat::parse_str(
+            r#"(module (func (export "main") (result i32) (i32.add (i32.const 1) (i32.const 2))))"#,
+        )
+        .unwrap();
+        let mut module = Module::read(&input).unwrap();
+
+        let mut profile = OptimizeProfile {
+            features: Some(Features::SIGN_EXT),
+            converge: Some(3),
+            extra_passes: vec!["vacuum".to_string()],
+            validate: true,
+            ..OptimizeProfile::default()
+        };
+        profile.codegen_config.optimization_level = 2;
+        module.apply_profile(&profile).unwrap();
+        assert_eq!(module.features(), Features::SIGN_EXT);
+        assert!(module.write_text().contains("(i32.const 3)"));
+
+        profile.extra_passes.push("invalid".to_string());
+        match module.apply_profile(&profile) {
+            Err(TransformError::InvalidPass(err)) => assert_eq!(err.pass(), "invalid"),
+            _ => panic!("expected an invalid pass error"),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let pipeline = PassPipeline::new(
+            &["simplify-locals", "vacuum"],
+            CodegenConfig {
+                optimization_level: 2,
+                ..CodegenConfig::default()
+            },
+        )
+        .unwrap();
+        let json = serde_json::to_string(&pipeline).unwrap();
+        let deserialized: PassPipeline = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.passes(), pipeline.passes());
+        assert_eq!(deserialized.codegen_config().optimization_level, 2);
+
+        let defaults: PassPipeline = serde_json::from_str(r#"{"passes": ["vacuum"]}"#).unwrap();
+        assert_eq!(defaults.codegen_config().optimization_level, 0);
+
+        let invalid = serde_json::from_str::<PassPipeline>(r#"{"passes": ["invalid"]}"#);
+        assert!(invalid.is_err());
+    }
+}
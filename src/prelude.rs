@@ -0,0 +1,13 @@
+//! The most commonly used types, for glob importing.
+//!
+//! ```
+//! use binaryen::prelude::*;
+//!
+//! let mut module = Module::new();
+//! module.optimize(&OptLevel::Os.to_codegen_config());
+//! ```
+
+pub use crate::{
+    CodegenConfig, Expr, Feature, Features, Function, Literal, Module, ModuleBuilder, OptLevel,
+    ParseError, ReadError, TransformError, Ty, UnaryOp, ValidationError, ValueTy,
+};
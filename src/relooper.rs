@@ -0,0 +1,566 @@
+use crate::{Expr, InnerModule, Module};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{error, fmt, ptr};
+
+/// Source of the ids that tie blocks to the relooper they were created by.
+static NEXT_RELOOPER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Builds structured control flow out of a control flow graph of basic blocks.
+///
+/// Blocks are added with [`Relooper::add_block`] or [`Relooper::add_block_with_switch`] and
+/// connected with branches. [`Relooper::render`] then turns the graph into a single
+/// expression, e.g. to be used as a function body.
+///
+/// ```
+/// # use binaryen::{Literal, Module, Ty, ValueTy};
+/// let mut module = Module::new();
+/// let mut relooper = module.relooper();
+///
+/// // if (param 0) { local 1 = 1 } else { local 1 = 0 }
+/// let entry = relooper.add_block(module.nop(), None);
+/// let then = relooper.add_block(module.local_set(1, module.const_(Literal::I32(1))), None);
+/// let otherwise = relooper.add_block(module.local_set(1, module.const_(Literal::I32(0))), None);
+/// relooper.add_branch(entry, then, Some(module.local_get(0, ValueTy::I32)), None);
+/// relooper.add_branch(entry, otherwise, None, None);
+///
+/// let body = relooper.render(entry, 2).unwrap();
+/// body.print();
+/// let vars = [ValueTy::I32, ValueTy::I32];
+/// module.add_fn("branch", ValueTy::I32.into(), Ty::none(), &vars, body);
+/// module.assert_valid();
+/// ```
+///
+/// A module can have any number of reloopers at the same time, e.g. one per function being
+/// built. Binaryen only releases a relooper when rendering it, so dropping one without rendering
+/// leaks it.
+pub struct Relooper {
+    id: usize,
+    module: Rc<InnerModule>,
+    raw: binaryen_sys::RelooperRef,
+    blocks: Vec<BlockInfo>,
+    /// The first problem found with the graph, reported by `render`.
+    error: Option<RelooperError>,
+}
+
+/// What the relooper knows about the branches out of a block, to check the graph before
+/// handing it to Binaryen.
+struct BlockInfo {
+    raw: binaryen_sys::RelooperBlockRef,
+    label: Option<String>,
+    targets: Vec<usize>,
+    is_switch: bool,
+    default_branches: usize,
+}
+
+/// Identifies a block of a relooper in errors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockId {
+    /// The index of the block in the order the blocks were added to the relooper.
+    pub index: usize,
+    /// The label the block was added with, if any.
+    pub label: Option<String>,
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "block {} ({})", self.index, label),
+            None => write!(f, "block {}", self.index),
+        }
+    }
+}
+
+/// Error returned by [`Relooper::render`] for malformed control flow graphs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RelooperError {
+    /// There is more than one branch between the same two blocks.
+    DuplicateBranch { from: BlockId, to: BlockId },
+    /// A plain block has more than one branch without a condition.
+    MultipleUnconditionalBranches { block: BlockId },
+    /// A switch block has no default branch, i.e. one without indices.
+    MissingDefaultBranch { block: BlockId },
+    /// A switch block has more than one default branch.
+    MultipleDefaultBranches { block: BlockId },
+}
+
+impl fmt::Display for RelooperError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RelooperError::DuplicateBranch { from, to } => {
+                write!(f, "more than one branch from {} to {}", from, to)
+            }
+            RelooperError::MultipleUnconditionalBranches { block } => {
+                write!(f, "{} has more than one unconditional branch", block)
+            }
+            RelooperError::MissingDefaultBranch { block } => {
+                write!(f, "switch {} has no default branch", block)
+            }
+            RelooperError::MultipleDefaultBranches { block } => {
+                write!(f, "switch {} has more than one default branch", block)
+            }
+        }
+    }
+}
+
+impl error::Error for RelooperError {}
+
+/// A block that leaves through regular branches, see [`Relooper::add_block`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlainBlock {
+    relooper_id: usize,
+    index: usize,
+}
+
+/// A block that leaves through a switch on a condition, see [`Relooper::add_block_with_switch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SwitchBlock {
+    relooper_id: usize,
+    index: usize,
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A block of a [`Relooper`], the target of a branch.
+pub trait Block: private::Sealed {
+    #[doc(hidden)]
+    fn relooper_id(&self) -> usize;
+    #[doc(hidden)]
+    fn index(&self) -> usize;
+}
+
+impl private::Sealed for PlainBlock {}
+
+impl Block for PlainBlock {
+    fn relooper_id(&self) -> usize {
+        self.relooper_id
+    }
+
+    fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl private::Sealed for SwitchBlock {}
+
+impl Block for SwitchBlock {
+    fn relooper_id(&self) -> usize {
+        self.relooper_id
+    }
+
+    fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl Module {
+    /// Create a relooper for building the control flow of a function body.
+    pub fn relooper(&self) -> Relooper {
+        Relooper {
+            id: NEXT_RELOOPER_ID.fetch_add(1, Ordering::Relaxed),
+            module: Rc::clone(&self.inner),
+            raw: unsafe { binaryen_sys::RelooperCreate(self.inner.raw) },
+            blocks: vec![],
+            error: None,
+        }
+    }
+}
+
+impl fmt::Debug for Relooper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        struct DebugBlock<'a>(&'a Relooper, usize);
+
+        impl<'a> fmt::Debug for DebugBlock<'a> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let info = &self.0.blocks[self.1];
+                let targets: Vec<_> = info.targets.iter().map(|&to| self.0.block_id(to)).collect();
+                f.debug_struct("Block")
+                    .field("index", &self.1)
+                    .field("label", &info.label)
+                    .field("targets", &targets)
+                    .finish()
+            }
+        }
+
+        let blocks: Vec<_> = (0..self.blocks.len())
+            .map(|index| DebugBlock(self, index))
+            .collect();
+        f.debug_struct("Relooper").field("blocks", &blocks).finish()
+    }
+}
+
+impl Relooper {
+    fn take_expr(&self, expr: Expr) -> binaryen_sys::BinaryenExpressionRef {
+        assert!(
+            expr.is_from(&self.module),
+            "expression belongs to another module"
+        );
+        expr.into_raw()
+    }
+
+    fn take_optional_expr(&self, expr: Option<Expr>) -> binaryen_sys::BinaryenExpressionRef {
+        expr.map_or(ptr::null_mut(), |expr| self.take_expr(expr))
+    }
+
+    fn push_block(
+        &mut self,
+        raw: binaryen_sys::RelooperBlockRef,
+        label: Option<&str>,
+        is_switch: bool,
+    ) -> usize {
+        self.blocks.push(BlockInfo {
+            raw,
+            label: label.map(str::to_string),
+            targets: vec![],
+            is_switch,
+            default_branches: 0,
+        });
+        self.blocks.len() - 1
+    }
+
+    fn block_id(&self, index: usize) -> BlockId {
+        BlockId {
+            index,
+            label: self.blocks[index].label.clone(),
+        }
+    }
+
+    /// Record a branch, returning `false` if it must not be handed to Binaryen.
+    fn record_branch(&mut self, from: usize, to: usize, is_default: bool) -> bool {
+        if self.blocks[from].targets.contains(&to) {
+            // Binaryen asserts that there is at most one branch to each target.
+            if self.error.is_none() {
+                self.error = Some(RelooperError::DuplicateBranch {
+                    from: self.block_id(from),
+                    to: self.block_id(to),
+                });
+            }
+            return false;
+        }
+        let info = &mut self.blocks[from];
+        info.targets.push(to);
+        if is_default {
+            info.default_branches += 1;
+        }
+        true
+    }
+
+    fn is_block_from_same_relooper<B: Block>(&self, block: &B) -> bool {
+        block.relooper_id() == self.id
+    }
+
+    fn raw_block<B: Block>(&self, block: &B) -> binaryen_sys::RelooperBlockRef {
+        debug_assert!(
+            self.is_block_from_same_relooper(block),
+            "block belongs to another relooper"
+        );
+        self.blocks[block.index()].raw
+    }
+
+    /// Add a block running `code`, which leaves through branches added with
+    /// [`Relooper::add_branch`].
+    ///
+    /// The `label` only serves debugging: it shows up in the `Debug` output of the relooper and
+    /// in errors, but not in the rendered code.
+    pub fn add_block(&mut self, code: Expr, label: Option<&str>) -> PlainBlock {
+        let code = self.take_expr(code);
+        let raw = unsafe { binaryen_sys::RelooperAddBlock(self.raw, code) };
+        PlainBlock {
+            relooper_id: self.id,
+            index: self.push_block(raw, label, false),
+        }
+    }
+
+    /// Add a block running `code`, which leaves through a switch on the i32 `condition`, with
+    /// the targets added with [`Relooper::add_branch_for_switch`].
+    ///
+    /// The `label` only serves debugging, like for [`Relooper::add_block`].
+    pub fn add_block_with_switch(
+        &mut self,
+        code: Expr,
+        condition: Expr,
+        label: Option<&str>,
+    ) -> SwitchBlock {
+        let code = self.take_expr(code);
+        let condition = self.take_expr(condition);
+        let raw = unsafe { binaryen_sys::RelooperAddBlockWithSwitch(self.raw, code, condition) };
+        SwitchBlock {
+            relooper_id: self.id,
+            index: self.push_block(raw, label, true),
+        }
+    }
+
+    /// Add a branch from `from` to `to`, running `code` on the way if given.
+    ///
+    /// The branch is taken if the i32 `condition` is non-zero. A branch without a condition is
+    /// taken if no other branch is, so each block must have at most one. There must be at most
+    /// one branch between two blocks, otherwise [`Relooper::render`] fails.
+    pub fn add_branch<B: Block>(
+        &mut self,
+        from: PlainBlock,
+        to: B,
+        condition: Option<Expr>,
+        code: Option<Expr>,
+    ) {
+        let (raw_from, raw_to) = (self.raw_block(&from), self.raw_block(&to));
+        let condition = self.take_optional_expr(condition);
+        let code = self.take_optional_expr(code);
+        if !self.record_branch(from.index, to.index(), condition.is_null()) {
+            return;
+        }
+        unsafe { binaryen_sys::RelooperAddBranch(raw_from, raw_to, condition, code) }
+    }
+
+    /// Add a branch from the switch `from` to `to`, running `code` on the way if given.
+    ///
+    /// The branch is taken if the condition of the switch is one of `indices`. A branch without
+    /// indices is the default, taken if no other one is. Each switch must have exactly one
+    /// default, and there must be at most one branch between two blocks.
+    pub fn add_branch_for_switch<B: Block>(
+        &mut self,
+        from: SwitchBlock,
+        to: B,
+        indices: &[u32],
+        code: Option<Expr>,
+    ) {
+        let (raw_from, raw_to) = (self.raw_block(&from), self.raw_block(&to));
+        let code = self.take_optional_expr(code);
+        if !self.record_branch(from.index, to.index(), indices.is_empty()) {
+            return;
+        }
+        // NOTE: RelooperAddBranchForSwitch expects a mutable ptr
+        let mut indices = indices.to_vec();
+        unsafe {
+            binaryen_sys::RelooperAddBranchForSwitch(
+                raw_from,
+                raw_to,
+                indices.as_mut_ptr(),
+                indices.len() as u32,
+                code,
+            )
+        }
+    }
+
+    fn check(&self) -> Result<(), RelooperError> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        for (index, info) in self.blocks.iter().enumerate() {
+            let block = self.block_id(index);
+            match (info.is_switch, info.default_branches) {
+                (false, 2..) => return Err(RelooperError::MultipleUnconditionalBranches { block }),
+                (true, 0) => return Err(RelooperError::MissingDefaultBranch { block }),
+                (true, 2..) => return Err(RelooperError::MultipleDefaultBranches { block }),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Render the control flow graph starting at `entry` into structured control flow.
+    ///
+    /// `label_helper` is the index of an i32 local the rendered code may use to track which
+    /// block to go to; it must be declared by the function the result is used in.
+    ///
+    /// Returns `Err` if the graph is malformed, see [`RelooperError`]. The relooper is leaked in
+    /// that case, since Binaryen can only release it by rendering.
+    pub fn render<B: Block>(self, entry: B, label_helper: u32) -> Result<Expr, RelooperError> {
+        self.check()?;
+        let raw = unsafe {
+            binaryen_sys::RelooperRenderAndDispose(self.raw, self.raw_block(&entry), label_helper)
+        };
+        Ok(Expr::from_inner(&self.module, raw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ty, ValueTy};
+
+    #[test]
+    fn test_switch() {
+        let mut module = Module::new();
+        let mut relooper = module.relooper();
+
+        let entry =
+            relooper.add_block_with_switch(module.nop(), module.local_get(0, ValueTy::I32), None);
+        let one = relooper.add_block(module.local_set(1, module.i32_const(10)), None);
+        let two = relooper.add_block(module.local_set(1, module.i32_const(20)), None);
+        let default = relooper.add_block(module.local_set(1, module.i32_const(0)), None);
+        relooper.add_branch_for_switch(entry, one, &[1], None);
+        relooper.add_branch_for_switch(entry, two, &[2, 3], None);
+        relooper.add_branch_for_switch(entry, default, &[], None);
+
+        let body = relooper.render(entry, 2).unwrap();
+        let vars = [ValueTy::I32, ValueTy::I32];
+        module.add_fn("switch", ValueTy::I32.into(), Ty::none(), &vars, body);
+        module.assert_valid();
+        assert!(module.write_text().contains("br_table"));
+    }
+
+    #[test]
+    fn test_two_reloopers() {
+        let mut module = Module::new();
+
+        // Build both graphs interleaved, and render them in the opposite order.
+        let mut first = module.relooper();
+        let mut second = module.relooper();
+        let first_entry = first.add_block(module.local_set(1, module.i32_const(1)), None);
+        let second_entry = second.add_block(module.nop(), None);
+        let first_then = first.add_block(module.local_set(1, module.i32_const(3)), None);
+        let second_exit = second.add_block(module.local_set(1, module.i32_const(2)), None);
+        let first_exit = first.add_block(module.nop(), None);
+        second.add_branch(second_entry, second_exit, None, None);
+        first.add_branch(
+            first_entry,
+            first_then,
+            Some(module.local_get(0, ValueTy::I32)),
+            None,
+        );
+        first.add_branch(first_entry, first_exit, None, None);
+        first.add_branch(first_then, first_exit, None, None);
+
+        let vars = [ValueTy::I32, ValueTy::I32];
+        let second_body = second.render(second_entry, 2).unwrap();
+        let first_body = first.render(first_entry, 2).unwrap();
+        module.add_fn("first", ValueTy::I32.into(), Ty::none(), &vars, first_body);
+        module.add_fn(
+            "second",
+            ValueTy::I32.into(),
+            Ty::none(),
+            &vars,
+            second_body,
+        );
+        module.assert_valid();
+
+        let text = module.write_text();
+        assert!(text.contains("(i32.const 1)"), "{}", text);
+        assert!(text.contains("(i32.const 2)"), "{}", text);
+        assert!(text.contains("(i32.const 3)"), "{}", text);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "block belongs to another relooper")]
+    fn test_block_from_other_relooper() {
+        let module = Module::new();
+        let mut first = module.relooper();
+        let mut second = module.relooper();
+        let from = first.add_block(module.nop(), None);
+        let _ = first.add_block(module.nop(), None);
+        let to = second.add_block(module.nop(), None);
+        // `to` has a valid index into `first`, so only the id tells them apart.
+        first.add_branch(from, to, None, None);
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let module = Module::new();
+
+        let mut relooper = module.relooper();
+        let entry = relooper.add_block(module.nop(), None);
+        let a = relooper.add_block(module.nop(), None);
+        let b = relooper.add_block(module.nop(), None);
+        relooper.add_branch(entry, a, None, None);
+        relooper.add_branch(entry, b, None, None);
+        let block = BlockId {
+            index: 0,
+            label: None,
+        };
+        assert_eq!(
+            relooper.render(entry, 0).err(),
+            Some(RelooperError::MultipleUnconditionalBranches { block })
+        );
+
+        let mut relooper = module.relooper();
+        let entry = relooper.add_block(module.nop(), Some("entry"));
+        let exit = relooper.add_block(module.nop(), Some("exit"));
+        relooper.add_branch(entry, exit, Some(module.local_get(0, ValueTy::I32)), None);
+        relooper.add_branch(entry, exit, None, None);
+        let error = relooper.render(entry, 0).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "more than one branch from block 0 (entry) to block 1 (exit)"
+        );
+    }
+
+    #[test]
+    fn test_switch_default() {
+        let module = Module::new();
+
+        let mut relooper = module.relooper();
+        let condition = module.local_get(0, ValueTy::I32);
+        let entry = relooper.add_block_with_switch(module.nop(), condition, Some("dispatch"));
+        let case = relooper.add_block(module.nop(), None);
+        relooper.add_branch_for_switch(entry, case, &[0, 1], None);
+        let error = relooper.render(entry, 0).err().unwrap();
+        assert_eq!(
+            error,
+            RelooperError::MissingDefaultBranch {
+                block: BlockId {
+                    index: 0,
+                    label: Some("dispatch".to_string()),
+                },
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "switch block 0 (dispatch) has no default branch"
+        );
+
+        let mut relooper = module.relooper();
+        let condition = module.local_get(0, ValueTy::I32);
+        let entry = relooper.add_block_with_switch(module.nop(), condition, None);
+        let a = relooper.add_block(module.nop(), None);
+        let b = relooper.add_block(module.nop(), None);
+        relooper.add_branch_for_switch(entry, a, &[], None);
+        relooper.add_branch_for_switch(entry, b, &[], None);
+        assert!(matches!(
+            relooper.render(entry, 0),
+            Err(RelooperError::MultipleDefaultBranches { .. })
+        ));
+    }
+
+    #[test]
+    fn test_debug_labels() {
+        let module = Module::new();
+        let mut relooper = module.relooper();
+        let entry = relooper.add_block(module.nop(), Some("entry"));
+        let exit = relooper.add_block(module.nop(), None);
+        relooper.add_branch(entry, exit, None, None);
+
+        let debug = format!("{:?}", relooper);
+        assert!(debug.contains(r#"label: Some("entry")"#), "{}", debug);
+        assert!(
+            debug.contains("targets: [BlockId { index: 1, label: None }]"),
+            "{}",
+            debug
+        );
+    }
+
+    #[test]
+    fn test_loop() {
+        let mut module = Module::new();
+        let mut relooper = module.relooper();
+
+        // Loop back to the entry as long as param 0 is non-zero.
+        let entry = relooper.add_block(module.nop(), None);
+        let exit = relooper.add_block(module.nop(), None);
+        relooper.add_branch(entry, entry, Some(module.local_get(0, ValueTy::I32)), None);
+        relooper.add_branch(entry, exit, None, None);
+
+        let body = relooper.render(entry, 1).unwrap();
+        module.add_fn(
+            "spin",
+            ValueTy::I32.into(),
+            Ty::none(),
+            &[ValueTy::I32],
+            body,
+        );
+        module.assert_valid();
+        assert!(module.write_text().contains("loop"));
+    }
+}
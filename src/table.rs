@@ -0,0 +1,200 @@
+use crate::{name_from_raw, Module, Ty};
+use std::marker::PhantomData;
+
+/// A table of a module, defined or imported.
+///
+/// Like [`Export`](crate::Export), this borrows the module it belongs to.
+#[derive(Clone, Copy)]
+pub struct Table<'a> {
+    raw: binaryen_sys::BinaryenTableRef,
+    _module: PhantomData<&'a Module>,
+}
+
+impl<'a> Table<'a> {
+    /// The internal name of the table.
+    pub fn name(&self) -> &'a str {
+        unsafe { name_from_raw(binaryen_sys::BinaryenTableGetName(self.raw)) }
+    }
+
+    /// The initial number of elements of the table.
+    pub fn initial(&self) -> u32 {
+        unsafe { binaryen_sys::BinaryenTableGetInitial(self.raw) }
+    }
+
+    /// The number of elements the table can grow to, if limited.
+    pub fn maximum(&self) -> Option<u32> {
+        unsafe {
+            if binaryen_sys::BinaryenTableHasMax(self.raw) {
+                Some(binaryen_sys::BinaryenTableGetMax(self.raw))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// The type of the elements of the table, e.g. [`Ty::funcref`].
+    pub fn element_type(&self) -> Ty {
+        Ty::from_raw(unsafe { binaryen_sys::BinaryenTableGetType(self.raw) })
+    }
+}
+
+impl<'a> std::fmt::Debug for Table<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Table")
+            .field("name", &self.name())
+            .field("initial", &self.initial())
+            .field("maximum", &self.maximum())
+            .field("element_type", &self.element_type())
+            .finish()
+    }
+}
+
+/// An active element segment of a module, which initializes part of a table when the module
+/// is instantiated. See [`Module::element_segments`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElementSegment {
+    /// The name of the table the segment initializes.
+    pub table: String,
+    /// The index of the first table element the segment initializes, or `None` if the offset
+    /// is not a constant, e.g. a `global.get` of an imported global.
+    pub offset: Option<u32>,
+    /// The functions the elements refer to, in order.
+    ///
+    /// Elements that aren't function references, like `ref.null`, are `None`, so that the
+    /// element at table index `offset + i` is always the `i`th entry.
+    pub func_names: Vec<Option<String>>,
+}
+
+impl ElementSegment {
+    unsafe fn from_raw(raw: binaryen_sys::BinaryenElementSegmentRef) -> ElementSegment {
+        let offset = binaryen_sys::BinaryenElementSegmentGetOffset(raw);
+        let is_const = binaryen_sys::BinaryenExpressionGetId(offset)
+            == binaryen_sys::BinaryenConstId()
+            && binaryen_sys::BinaryenExpressionGetType(offset) == binaryen_sys::BinaryenTypeInt32();
+        let func_names = (0..binaryen_sys::BinaryenElementSegmentGetLength(raw))
+            .map(|index| {
+                let name = binaryen_sys::BinaryenShimElementSegmentGetFunction(raw, index);
+                if name.is_null() {
+                    None
+                } else {
+                    Some(name_from_raw(name).to_string())
+                }
+            })
+            .collect();
+        ElementSegment {
+            table: name_from_raw(binaryen_sys::BinaryenElementSegmentGetTable(raw)).to_string(),
+            offset: if is_const {
+                Some(binaryen_sys::BinaryenConstGetValueI32(offset) as u32)
+            } else {
+                None
+            },
+            func_names,
+        }
+    }
+}
+
+impl Module {
+    /// The number of tables of the module, including imported ones.
+    pub fn num_tables(&self) -> u32 {
+        unsafe { binaryen_sys::BinaryenGetNumTables(self.inner.raw) }
+    }
+
+    /// Iterate over the tables of the module, in order, including imported ones.
+    pub fn tables(&self) -> impl Iterator<Item = Table<'_>> + '_ {
+        let raw_module = self.inner.raw;
+        (0..self.num_tables()).map(move |index| Table {
+            raw: unsafe { binaryen_sys::BinaryenGetTableByIndex(raw_module, index) },
+            _module: PhantomData,
+        })
+    }
+
+    /// Get the active element segments of the module, in order.
+    ///
+    /// Together they tell which functions `call_indirect` can reach through which table index,
+    /// at least for the tables that aren't changed at runtime. Passive segments, which are
+    /// only copied into tables by `table.init`, are left out.
+    pub fn element_segments(&self) -> Vec<ElementSegment> {
+        let raw_module = self.inner.raw;
+        unsafe {
+            (0..binaryen_sys::BinaryenGetNumElementSegments(raw_module))
+                .map(|index| binaryen_sys::BinaryenGetElementSegmentByIndex(raw_module, index))
+                .filter(|raw| !binaryen_sys::BinaryenElementSegmentIsPassive(*raw))
+                .map(|raw| ElementSegment::from_raw(raw))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables() {
+        const TABLES: &'static str = r#"
+            (module
+                (import "env" "table" (table $imported 1 funcref))
+                (table $funcs 2 10 funcref)
+                (table $externs 0 externref)
+            )
+        "#;
+        let module = Module::read(&wat::parse_str(TABLES).unwrap()).unwrap();
+        assert_eq!(module.num_tables(), 3);
+
+        let tables: Vec<_> = module
+            .tables()
+            .map(|table| {
+                (
+                    table.name(),
+                    table.initial(),
+                    table.maximum(),
+                    table.element_type(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            tables,
+            [
+                ("imported", 1, None, Ty::funcref()),
+                ("funcs", 2, Some(10), Ty::funcref()),
+                ("externs", 0, None, Ty::externref()),
+            ]
+        );
+
+        assert_eq!(Module::new().tables().count(), 0);
+    }
+
+    #[test]
+    fn test_element_segments() {
+        const ELEMENTS: &'static str = r#"
+            (module
+                (import "env" "base" (global $base i32))
+                (table $funcs 8 funcref)
+                (func $a)
+                (func $b)
+                (elem (table $funcs) (i32.const 2) func $a $b $a)
+                (elem (table $funcs) (global.get $base) funcref (ref.null func) (ref.func $b))
+                (elem func $b)
+            )
+        "#;
+        let module = Module::read(&wat::parse_str(ELEMENTS).unwrap()).unwrap();
+        let name = |name: &str| Some(name.to_string());
+        assert_eq!(
+            module.element_segments(),
+            [
+                ElementSegment {
+                    table: "funcs".to_string(),
+                    offset: Some(2),
+                    func_names: vec![name("a"), name("b"), name("a")],
+                },
+                ElementSegment {
+                    table: "funcs".to_string(),
+                    offset: None,
+                    func_names: vec![None, name("b")],
+                },
+            ]
+        );
+
+        assert!(Module::new().element_segments().is_empty());
+    }
+}
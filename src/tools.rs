@@ -1,36 +1,99 @@
-use crate::Module;
+use crate::{Features, Module};
 use std::os::raw::c_char;
 
-/// Convert some random array of bytes to a Module.
-pub fn translate_to_fuzz(seed: &[u8]) -> Module {
-    if seed.len() == 0 {
-        return Module::new();
-    }
+/// Options for [`translate_to_fuzz_into`].
+///
+/// The version of Binaryen's fuzzer bundled with this crate has no closed-world mode, so
+/// there is no option for it. To optimize a fuzzed module under closed-world assumptions, set
+/// [`CodegenConfig::closed_world`](crate::CodegenConfig::closed_world) on the configuration
+/// it's optimized with.
+#[derive(Clone, Debug)]
+pub struct FuzzOptions {
+    /// The features enabled on the module. The fuzzer only emits instructions and types of
+    /// enabled features, e.g. atomic operations with [`Features::ATOMICS`].
+    pub features: Features,
+    /// Allow the module to produce NaNs. If disabled, the `denan` pass is run on the module,
+    /// which replaces NaN constants and the NaN results of operations by zero at runtime. This
+    /// avoids differences between engines, which are free to pick NaN bit patterns.
+    ///
+    /// Enabled by default.
+    pub allow_nan: bool,
+}
 
-    unsafe {
-        let raw_module =
-            binaryen_sys::translateToFuzz(seed.as_ptr() as *const c_char, seed.len(), true);
-        Module::from_raw(raw_module)
+impl Default for FuzzOptions {
+    fn default() -> FuzzOptions {
+        FuzzOptions {
+            features: Features::MVP,
+            allow_nan: true,
+        }
     }
 }
 
+/// Convert some random array of bytes to a Module.
+///
+/// Only the atomics feature is enabled on top of the MVP, not all features. See
+/// [`translate_to_fuzz_into`] for other features, and for how stable the module is for a given
+/// seed.
+pub fn translate_to_fuzz(seed: &[u8]) -> Module {
+    let options = FuzzOptions {
+        features: Features::ATOMICS,
+        ..FuzzOptions::default()
+    };
+    translate_to_fuzz_into(seed, &options)
+}
+
 /// Convert some random array of bytes to a WASM-MVP-only Module.
 pub fn translate_to_fuzz_mvp(seed: &[u8]) -> Module {
-    if seed.len() == 0 {
-        return Module::new();
-    }
+    translate_to_fuzz_into(seed, &FuzzOptions::default())
+}
 
-    unsafe {
-        let raw_module =
-            binaryen_sys::translateToFuzz(seed.as_ptr() as *const c_char, seed.len(), false);
-        Module::from_raw(raw_module)
+/// Convert some random array of bytes to a module in the text format, e.g. to seed the corpus
+/// of a fuzzer for text tooling.
+///
+/// The module is generated like by [`translate_to_fuzz`]. The text records the features the
+/// module uses, so [`Module::parse_text`] turns it back into a valid module.
+pub fn translate_to_fuzz_text(seed: &[u8]) -> String {
+    translate_to_fuzz(seed).write_text()
+}
+
+/// Convert some random array of bytes to a Module using the given options.
+///
+/// The module only depends on the seed and the options: with the same version of Binaryen, the
+/// same seed always produces the same module, e.g. to keep a regression corpus as seeds. This
+/// holds for all the fuzzing functions of this module. Upgrading Binaryen may change the
+/// generated modules though, so seeds are not portable across versions of this crate that
+/// bundle a different Binaryen.
+///
+/// The options are taken by reference so a fuzzing loop can reuse the same options for
+/// every iteration.
+pub fn translate_to_fuzz_into(seed: &[u8], options: &FuzzOptions) -> Module {
+    let mut module = if seed.is_empty() {
+        let mut module = Module::new();
+        module.set_features(options.features);
+        module
+    } else {
+        unsafe {
+            let raw_module = binaryen_sys::BinaryenShimTranslateToFuzz(
+                seed.as_ptr() as *const c_char,
+                seed.len(),
+                options.features.to_raw(),
+            );
+            Module::from_raw(raw_module)
+        }
+    };
+    if !options.allow_nan {
+        module.run_pass_with_arguments("denan", &[]);
     }
+    module
 }
 
 #[cfg(test)]
 mod tests {
     use super::translate_to_fuzz;
     use super::translate_to_fuzz_mvp;
+    use super::translate_to_fuzz_text;
+    use super::{translate_to_fuzz_into, FuzzOptions};
+    use crate::{Features, Module};
     use rand::{self, RngCore};
 
     #[test]
@@ -56,4 +119,92 @@ mod tests {
             assert!(module.is_valid());
         }
     }
+
+    #[test]
+    fn test_translate_to_fuzz_into() {
+        let options = FuzzOptions::default();
+        let mut seed = vec![0; 1000];
+        for _ in 0..100 {
+            let mut rng = rand::thread_rng();
+            rng.fill_bytes(&mut seed);
+            let module = translate_to_fuzz_into(&seed, &options);
+
+            assert!(module.is_valid());
+        }
+
+        assert!(translate_to_fuzz_into(&[], &options).is_valid());
+    }
+
+    #[test]
+    fn test_translate_to_fuzz_features() {
+        let mut seed = vec![0; 1000];
+        for features in [Features::MVP, Features::SIMD | Features::BULK_MEMORY] {
+            let options = FuzzOptions {
+                features,
+                ..FuzzOptions::default()
+            };
+            for _ in 0..20 {
+                let mut rng = rand::thread_rng();
+                rng.fill_bytes(&mut seed);
+                let module = translate_to_fuzz_into(&seed, &options);
+
+                assert_eq!(module.features(), features);
+                assert!(module.validate_with_features(features).is_ok());
+            }
+        }
+        assert_eq!(translate_to_fuzz(&seed).features(), Features::ATOMICS);
+        assert_eq!(translate_to_fuzz_mvp(&seed).features(), Features::MVP);
+    }
+
+    #[test]
+    fn test_translate_to_fuzz_no_nan() {
+        let options = FuzzOptions {
+            allow_nan: false,
+            ..FuzzOptions::default()
+        };
+        let mut seed = vec![0; 1000];
+        for _ in 0..20 {
+            let mut rng = rand::thread_rng();
+            rng.fill_bytes(&mut seed);
+            let module = translate_to_fuzz_into(&seed, &options);
+
+            assert!(module.is_valid());
+            let text = module.write_text();
+            assert!(!text.contains(" nan") && !text.contains("-nan"), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_translate_to_fuzz_deterministic() {
+        let mut seed = vec![0; 1000];
+        for _ in 0..10 {
+            let mut rng = rand::thread_rng();
+            rng.fill_bytes(&mut seed);
+
+            assert_eq!(
+                translate_to_fuzz(&seed).write(),
+                translate_to_fuzz(&seed).write()
+            );
+            assert_eq!(
+                translate_to_fuzz_mvp(&seed).write(),
+                translate_to_fuzz_mvp(&seed).write()
+            );
+            assert_eq!(translate_to_fuzz_text(&seed), translate_to_fuzz_text(&seed));
+        }
+    }
+
+    #[test]
+    fn test_translate_to_fuzz_text() {
+        let mut seed = vec![0; 1000];
+        for _ in 0..100 {
+            let mut rng = rand::thread_rng();
+            rng.fill_bytes(&mut seed);
+            let text = translate_to_fuzz_text(&seed);
+
+            let module = Module::parse_text(&text).unwrap();
+            assert!(module.is_valid(), "{}", text);
+        }
+
+        assert!(Module::parse_text(&translate_to_fuzz_text(&[])).is_ok());
+    }
 }
@@ -0,0 +1,166 @@
+/// The type of a single value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValueTy {
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+}
+
+impl ValueTy {
+    pub(crate) fn to_raw(self) -> binaryen_sys::BinaryenType {
+        unsafe {
+            match self {
+                ValueTy::I32 => binaryen_sys::BinaryenTypeInt32(),
+                ValueTy::I64 => binaryen_sys::BinaryenTypeInt64(),
+                ValueTy::F32 => binaryen_sys::BinaryenTypeFloat32(),
+                ValueTy::F64 => binaryen_sys::BinaryenTypeFloat64(),
+                ValueTy::V128 => binaryen_sys::BinaryenTypeVec128(),
+            }
+        }
+    }
+
+    /// The value type of `raw`, or `None` for types that are not value types of this crate,
+    /// like tuples and reference types.
+    pub(crate) fn from_raw(raw: binaryen_sys::BinaryenType) -> Option<ValueTy> {
+        [
+            ValueTy::I32,
+            ValueTy::I64,
+            ValueTy::F32,
+            ValueTy::F64,
+            ValueTy::V128,
+        ]
+        .iter()
+        .copied()
+        .find(|ty| ty.to_raw() == raw)
+    }
+}
+
+/// The type of an expression, or of the params or results of a function.
+///
+/// This is either no value at all, a single value or a tuple of values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Ty {
+    raw: binaryen_sys::BinaryenType,
+}
+
+impl Ty {
+    /// The type of expressions that produce no value.
+    pub fn none() -> Ty {
+        Ty {
+            raw: unsafe { binaryen_sys::BinaryenTypeNone() },
+        }
+    }
+
+    /// The type of a single value.
+    pub fn value(ty: ValueTy) -> Ty {
+        Ty { raw: ty.to_raw() }
+    }
+
+    /// The type of references to functions, e.g. the elements of a table for `call_indirect`.
+    pub fn funcref() -> Ty {
+        Ty {
+            raw: unsafe { binaryen_sys::BinaryenTypeFuncref() },
+        }
+    }
+
+    /// The type of references to host values.
+    pub fn externref() -> Ty {
+        Ty {
+            raw: unsafe { binaryen_sys::BinaryenTypeExternref() },
+        }
+    }
+
+    /// The type of a sequence of values, e.g. the params of a function.
+    ///
+    /// An empty sequence is [`Ty::none`] and a sequence of one is [`Ty::value`].
+    pub fn tuple(tys: &[ValueTy]) -> Ty {
+        let mut raw_tys: Vec<_> = tys.iter().map(|ty| ty.to_raw()).collect();
+        Ty {
+            raw: unsafe {
+                binaryen_sys::BinaryenTypeCreate(raw_tys.as_mut_ptr(), raw_tys.len() as u32)
+            },
+        }
+    }
+
+    /// The number of values of the type: 0 for [`Ty::none`], 1 for a single value and the
+    /// length of a tuple otherwise.
+    pub fn arity(&self) -> u32 {
+        unsafe { binaryen_sys::BinaryenTypeArity(self.raw) }
+    }
+
+    /// The types of the values of the type, in order, e.g. the results of a multi-value
+    /// function. [`Ty::none`] has no components and a single value, including a reference,
+    /// is its own only component.
+    pub fn components(&self) -> Vec<Ty> {
+        self.raw_components()
+            .into_iter()
+            .map(Ty::from_raw)
+            .collect()
+    }
+
+    pub(crate) fn raw_components(self) -> Vec<binaryen_sys::BinaryenType> {
+        // Nothing to expand, and no buffer to expand into.
+        if self == Ty::none() {
+            return vec![];
+        }
+        let mut raw_tys = vec![0; self.arity() as usize];
+        unsafe { binaryen_sys::BinaryenTypeExpand(self.raw, raw_tys.as_mut_ptr()) };
+        raw_tys
+    }
+
+    pub(crate) fn to_raw(self) -> binaryen_sys::BinaryenType {
+        self.raw
+    }
+
+    pub(crate) fn from_raw(raw: binaryen_sys::BinaryenType) -> Ty {
+        Ty { raw }
+    }
+}
+
+impl From<ValueTy> for Ty {
+    fn from(ty: ValueTy) -> Ty {
+        Ty::value(ty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuple() {
+        assert_eq!(Ty::tuple(&[]), Ty::none());
+        assert_eq!(Ty::tuple(&[ValueTy::I64]), Ty::value(ValueTy::I64));
+        assert_eq!(
+            Ty::tuple(&[ValueTy::I32, ValueTy::F64]),
+            Ty::tuple(&[ValueTy::I32, ValueTy::F64])
+        );
+        assert_ne!(
+            Ty::tuple(&[ValueTy::I32, ValueTy::F64]),
+            Ty::tuple(&[ValueTy::F64, ValueTy::I32])
+        );
+    }
+
+    #[test]
+    fn test_components() {
+        assert_eq!(Ty::none().arity(), 0);
+        assert_eq!(Ty::none().components(), []);
+
+        let single = Ty::value(ValueTy::V128);
+        assert_eq!(single.arity(), 1);
+        assert_eq!(single.components(), [single]);
+
+        assert_eq!(Ty::funcref().components(), [Ty::funcref()]);
+        assert_eq!(Ty::externref().components(), [Ty::externref()]);
+
+        let tys = [ValueTy::I32, ValueTy::F64, ValueTy::I32];
+        let tuple = Ty::tuple(&tys);
+        assert_eq!(tuple.arity(), 3);
+        assert_eq!(
+            tuple.components(),
+            tys.iter().copied().map(Ty::value).collect::<Vec<_>>()
+        );
+    }
+}